@@ -1,9 +1,54 @@
+use std::path::PathBuf;
+
 fn main() {
+    generate_room_layout();
+    // The library's tests run on the host, where there is no linker script to add.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("none") {
+        return;
+    }
     linker_be_nice();
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
 }
 
+// Writes the `room` module from the layout file named by `ROOM_LAYOUT`, or the one matching the
+// `office_lights` / `test_strip` feature. Relative paths are relative to this crate.
+fn generate_room_layout() {
+    println!("cargo::rustc-check-cfg=cfg(room_layout)");
+    println!("cargo::rerun-if-env-changed=ROOM_LAYOUT");
+    println!("cargo::rerun-if-changed=build.rs");
+
+    let path = match std::env::var_os("ROOM_LAYOUT") {
+        Some(path) => PathBuf::from(path),
+        None if std::env::var_os("CARGO_FEATURE_OFFICE_LIGHTS").is_some() => {
+            PathBuf::from("layouts/office_lights.toml")
+        }
+        None if std::env::var_os("CARGO_FEATURE_TEST_STRIP").is_some() => {
+            PathBuf::from("layouts/test_strip.toml")
+        }
+        None => return,
+    };
+    println!("cargo::rerun-if-changed={}", path.display());
+
+    let source = std::fs::read_to_string(&path).unwrap_or_else(|error| {
+        eprintln!(
+            "error: couldn't read room layout {}: {}",
+            path.display(),
+            error
+        );
+        std::process::exit(1);
+    });
+    let room = room_layout::parse(&source).unwrap_or_else(|error| {
+        eprintln!("error: bad room layout in {}: {}", path.display(), error);
+        std::process::exit(1);
+    });
+
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").expect("Cargo sets OUT_DIR."));
+    let generated = room_layout::generate(&room, &path.display().to_string());
+    std::fs::write(out_dir.join("room.rs"), generated).expect("Failed to write room.rs");
+    println!("cargo::rustc-cfg=room_layout");
+}
+
 fn linker_be_nice() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() > 1 {
@@ -0,0 +1,205 @@
+//! SPI output for clocked APA102 / SK9822 strips.
+//!
+//! These strips take a clock line alongside the data, so they're driven from the SPI peripheral
+//! instead of RMT. Every LED frame carries a 5-bit global brightness on top of the 8-bit colors,
+//! which drives the LEDs at a lower current rather than a shorter duty, so dim colors keep their
+//! full 8 bits of color resolution.
+//!
+//! A frame on the wire is:
+//! - a start frame of 32 zero bits,
+//! - one 32-bit frame per LED: `0b111` and the 5-bit brightness, then blue, green and red,
+//! - an end frame. The data is delayed by half a clock per LED, so at least `n / 2` extra clocks
+//!   are needed to push it to the end of the strip. SK9822s also latch on a 32 zero bit reset
+//!   frame, so that is sent first and the extra clocks follow as more zeros.
+
+use rgb::{RGB8, RGB16};
+use smart_leds::SmartLedsWriteAsync;
+
+/// SPI clock the strips are driven at. APA102s take up to around 20MHz, but long runs of cable
+/// between the board and the strip don't.
+pub const CLOCK_KHZ: u32 = 4_000;
+
+/// Highest value of the 5-bit global brightness field.
+pub const MAX_GLOBAL_BRIGHTNESS: u8 = 31;
+
+const START_FRAME_LEN: usize = 4;
+const LED_FRAME_LEN: usize = 4;
+const RESET_FRAME_LEN: usize = 4;
+
+/// Size in bytes of the end frame for a strip of `num_leds`.
+pub const fn end_frame_len(num_leds: usize) -> usize {
+    // One extra clock per two LEDs, rounded up to whole bytes.
+    RESET_FRAME_LEN + num_leds.div_ceil(16)
+}
+
+/// Size in bytes of a complete frame for a strip of `num_leds`. Use this to size the buffer
+/// handed to an `Apa102Adapter`.
+pub const fn frame_len(num_leds: usize) -> usize {
+    START_FRAME_LEN + num_leds * LED_FRAME_LEN + end_frame_len(num_leds)
+}
+
+/// The values sent for one LED.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Apa102Led {
+    pub color: RGB8,
+    /// Global brightness, `0..=MAX_GLOBAL_BRIGHTNESS`.
+    pub brightness: u8,
+}
+
+impl Apa102Led {
+    pub const fn new(color: RGB8, brightness: u8) -> Self {
+        Apa102Led { color, brightness }
+    }
+
+    /// Splits an 8.8 fixed point color, like the ones from `GammaLut::apply_fixed` or a
+    /// `LinearBuffer`, into the lowest global brightness that can show it and the 8-bit color at
+    /// that brightness. Dim colors end up with several more bits of resolution than rounding to
+    /// 8 bits would give.
+    pub const fn from_fixed(color: RGB16) -> Self {
+        let full_scale = 255 * 256;
+        let mut high = color.r as u32;
+        if color.g as u32 > high {
+            high = color.g as u32;
+        }
+        if color.b as u32 > high {
+            high = color.b as u32;
+        }
+        if high == 0 {
+            return Apa102Led { color: RGB8 { r: 0, g: 0, b: 0 }, brightness: 0 };
+        }
+        let mut brightness = (high * MAX_GLOBAL_BRIGHTNESS as u32).div_ceil(full_scale);
+        if brightness > MAX_GLOBAL_BRIGHTNESS as u32 {
+            brightness = MAX_GLOBAL_BRIGHTNESS as u32;
+        }
+        Apa102Led {
+            color: RGB8 {
+                r: scale_channel(color.r, brightness),
+                g: scale_channel(color.g, brightness),
+                b: scale_channel(color.b, brightness),
+            },
+            brightness: brightness as u8,
+        }
+    }
+
+    /// The 4 bytes sent for this LED.
+    pub const fn to_bytes(&self) -> [u8; LED_FRAME_LEN] {
+        let brightness = if self.brightness > MAX_GLOBAL_BRIGHTNESS {
+            MAX_GLOBAL_BRIGHTNESS
+        } else {
+            self.brightness
+        };
+        [
+            0b1110_0000 | brightness,
+            self.color.b,
+            self.color.g,
+            self.color.r,
+        ]
+    }
+}
+
+/// Rescales one 8.8 fixed point channel to 8 bits at a global brightness of `brightness`.
+const fn scale_channel(channel: u16, brightness: u32) -> u8 {
+    let value =
+        (channel as u32 * MAX_GLOBAL_BRIGHTNESS as u32 + brightness * 128) / (brightness * 256);
+    if value > 255 { 255 } else { value as u8 }
+}
+
+/// Writes a complete frame for `leds` into the start of `buffer` and returns its length, or
+/// `None` if `buffer` is too small to hold it.
+pub fn encode_frame(leds: impl IntoIterator<Item = Apa102Led>, buffer: &mut [u8]) -> Option<usize> {
+    buffer.get_mut(..START_FRAME_LEN)?.fill(0);
+    let mut len = START_FRAME_LEN;
+    let mut num_leds = 0;
+    for led in leds {
+        buffer
+            .get_mut(len..len + LED_FRAME_LEN)?
+            .copy_from_slice(&led.to_bytes());
+        len += LED_FRAME_LEN;
+        num_leds += 1;
+    }
+    let end = len + end_frame_len(num_leds);
+    buffer.get_mut(len..end)?.fill(0);
+    Some(end)
+}
+
+/// Anything that can clock bytes out to a strip.
+pub trait SpiWrite {
+    type Error;
+
+    fn write(&mut self, bytes: &[u8]) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+#[cfg(feature = "esp32c3")]
+impl SpiWrite for esp_hal::spi::master::Spi<'_, esp_hal::Async> {
+    type Error = esp_hal::spi::Error;
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_async(bytes).await
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Apa102Error<E> {
+    /// The adapter's buffer is smaller than `frame_len` for the number of LEDs written.
+    BufferTooSmall,
+    /// The SPI transfer failed.
+    Spi(E),
+}
+
+/// Drives an APA102 / SK9822 strip, in place of a `SmartLedsAdapterAsync` for RMT strips.
+///
+/// Plain `RGB8` writes, like the ones from `Output::colors`, send every LED at the adapter's
+/// global brightness. `write_leds` sets it per LED instead.
+pub struct Apa102Adapter<'a, S> {
+    spi: S,
+    buffer: &'a mut [u8],
+    brightness: u8,
+}
+
+impl<'a, S: SpiWrite> Apa102Adapter<'a, S> {
+    /// Creates an adapter writing through `spi`. `buffer` needs to hold `frame_len` bytes for the
+    /// number of LEDs on the strip.
+    pub fn new(spi: S, buffer: &'a mut [u8]) -> Self {
+        Apa102Adapter { spi, buffer, brightness: MAX_GLOBAL_BRIGHTNESS }
+    }
+
+    /// Sets the global brightness used for plain `RGB8` writes.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness.min(MAX_GLOBAL_BRIGHTNESS);
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Sends `leds`, each with its own global brightness.
+    pub async fn write_leds(
+        &mut self,
+        leds: impl IntoIterator<Item = Apa102Led>,
+    ) -> Result<(), Apa102Error<S::Error>> {
+        let len = encode_frame(leds, self.buffer).ok_or(Apa102Error::BufferTooSmall)?;
+        self.spi
+            .write(&self.buffer[..len])
+            .await
+            .map_err(Apa102Error::Spi)
+    }
+}
+
+impl<S: SpiWrite> SmartLedsWriteAsync for Apa102Adapter<'_, S> {
+    type Error = Apa102Error<S::Error>;
+    type Color = RGB8;
+
+    async fn write<T, I>(&mut self, iterator: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = I>,
+        I: Into<Self::Color>,
+    {
+        let brightness = self.brightness;
+        self.write_leds(
+            iterator
+                .into_iter()
+                .map(|color| Apa102Led::new(color.into(), brightness)),
+        )
+        .await
+    }
+}
@@ -2,37 +2,183 @@
 #![no_main]
 
 use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_time::Timer;
 use esp_alloc as _;
 use esp_backtrace as _;
-use esp_hal::gpio::{Input, InputConfig, Level};
-use esp_hal::time::{Duration, Instant, Rate};
+use esp_hal::gpio::{Input, InputConfig, Level, Output, OutputConfig};
+use esp_hal::time::Rate;
 use esp_hal::{clock::CpuClock, rmt::Rmt, rng::Rng};
-use esp_hal_smartled::{SmartLedsAdapter, smart_led_buffer};
 use esp_println::println;
-use lc::animations::{Animatable, Animation, RainbowDir};
-use lc::{LightingController, LogicalStrip, utility::default_translation_array};
-use lighting_controller::default_animations::ANI_DEFAULT;
+use lc::animations::{AnimationType, RainbowDir};
+use lc::{LightingController, LogicalStrip};
 use lighting_controller::{self as lc, animations};
 use rgb::RGB8;
-use smart_leds::{SmartLedsWrite as _, brightness, colors::*, gamma};
+use smart_leds::colors::*;
+
+#[cfg(not(room_layout))]
+compile_error!(
+    "No room layout selected, enable the `office_lights` or `test_strip` feature or set \
+     ROOM_LAYOUT to a layout file."
+);
+
+use esp32c3_smart_led_multi_pin::room::*;
+use esp32c3_smart_led_multi_pin::{
+    room_animations, room_loop_animation, room_outputs, room_psu_pin, room_reinit_outputs,
+    room_write_outputs,
+};
+
+use esp32c3_smart_led_multi_pin::button::{self, BUTTON_DEFAULT, Button};
+use esp32c3_smart_led_multi_pin::calibration::Calibration;
+use esp32c3_smart_led_multi_pin::combo::{Action, Combo};
+use esp32c3_smart_led_multi_pin::control::{AnimationMode, Command, CommandChannel, CommandSender};
+#[cfg(not(feature = "linear_pipeline"))]
+use esp32c3_smart_led_multi_pin::correction::apply_corrections;
+use esp32c3_smart_led_multi_pin::default_consts::*;
+use esp32c3_smart_led_multi_pin::dither::TemporalDither;
+#[cfg(not(feature = "linear_pipeline"))]
+use esp32c3_smart_led_multi_pin::dither::dither_outputs;
+use esp32c3_smart_led_multi_pin::error::{OutputError, WriteAction, WriteHealth};
+use esp32c3_smart_led_multi_pin::frame_scheduler::FrameScheduler;
+#[cfg(not(feature = "linear_pipeline"))]
+use esp32c3_smart_led_multi_pin::gamma::apply_luts;
+use esp32c3_smart_led_multi_pin::input::{InputChannel, InputEvent, InputReceiver, InputSender};
+#[cfg(feature = "linear_pipeline")]
+use esp32c3_smart_led_multi_pin::linear::LinearBuffer;
+use esp32c3_smart_led_multi_pin::psu::PsuControl;
+use esp32c3_smart_led_multi_pin::refresh::{StripRefresh, hash_colors};
+#[cfg(feature = "telemetry")]
+use esp32c3_smart_led_multi_pin::telemetry::Telemetry;
+use esp32c3_smart_led_multi_pin::transition::{Crossfade, Easing, background_offset, blend};
 
-#[cfg(feature = "office_lights")]
-use esp32c3_smart_led_multi_pin::office_lights::*;
+esp_bootloader_esp_idf::esp_app_desc!();
 
-#[cfg(feature = "test_strip")]
-use esp32c3_smart_led_multi_pin::test_strip::*;
+static INPUT_EVENTS: InputChannel = InputChannel::new();
+static COMMANDS: CommandChannel = CommandChannel::new();
 
-use esp32c3_smart_led_multi_pin::default_consts::*;
+fn now_us() -> u64 {
+    embassy_time::Instant::now().as_micros()
+}
 
-esp_bootloader_esp_idf::esp_app_desc!();
+// Runs one button. The task sleeps until the pin changes level or the button has a pending
+// debounce/long-press deadline, then forwards any resulting events to the control task.
+#[embassy_executor::task(pool_size = 3)]
+async fn button_task(id: u8, mut button: Button<Input<'static>>, events: InputSender) {
+    loop {
+        let now_us = now_us();
+        while let Some(event) = button.update(now_us) {
+            events.send(InputEvent::Button { id, event }).await;
+        }
 
-#[esp_rtos::main]
-async fn main(_spawner: Spawner) -> ! {
-    const NUM_LEDS_STRIP_CLOSET_WINDOW: usize = NUM_LEDS_CLOSET_WALL + NUM_LEDS_WINDOW_WALL; //strip 1, GPIO6
-    const NUM_LEDS_STRIP_DOOR_NORTH: usize = NUM_LEDS_DOOR_WALL + NUM_LEDS_NORTH_WALL; //strip 2, GPIO5
-    const NUM_LEDS: usize = NUM_LEDS_STRIP_CLOSET_WINDOW + NUM_LEDS_STRIP_DOOR_NORTH;
+        // Wait for the opposite of the current level rather than an edge, so a change that
+        // happened since the last sample still wakes us immediately.
+        let deadline_us = button.next_deadline_us();
+        let pin = button.source_mut();
+        let level_change = async {
+            if pin.is_high() {
+                pin.wait_for_low().await
+            } else {
+                pin.wait_for_high().await
+            }
+        };
+        match deadline_us {
+            Some(deadline_us) => {
+                let deadline = embassy_time::Instant::from_micros(deadline_us);
+                let _ = embassy_time::with_deadline(deadline, level_change).await;
+            }
+            None => level_change.await,
+        }
+    }
+}
+
+// Decides what each input means. This is the only place that knows which button does what, the
+// render loop just gets told what to change.
+#[embassy_executor::task]
+async fn control_task(inputs: InputReceiver, commands: CommandSender) {
+    // Pressing buttons 0 and 2 together switches calibration mode on and off. Their presses are
+    // held back for up to this long to see whether the other one follows.
+    const COMBO_WINDOW_US: u64 = 150_000;
+    let mut combo = Combo::new([0, 2], COMBO_WINDOW_US);
+    let mut calibrating = false;
+    loop {
+        let input = match combo.next_deadline_us() {
+            Some(deadline_us) => {
+                let deadline = embassy_time::Instant::from_micros(deadline_us);
+                embassy_time::with_deadline(deadline, inputs.receive())
+                    .await
+                    .ok()
+            }
+            None => Some(inputs.receive().await),
+        };
+        if let Some(input) = input {
+            combo.input(input, now_us());
+        }
+
+        while let Some(action) = combo.update(now_us()) {
+            // While calibration mode is on, buttons 0 and 2 step the cursor back and forward,
+            // repeating while held, and button 1 jumps it to the next wall.
+            let command = match action {
+                Action::Combo => {
+                    calibrating = !calibrating;
+                    Command::Calibrate(calibrating)
+                }
+                Action::Input(InputEvent::Button { id, event }) if calibrating => match event {
+                    button::Event::Pressed | button::Event::Held => match id {
+                        0 => Command::StepCursor(-1),
+                        2 => Command::StepCursor(1),
+                        1 if event == button::Event::Pressed => Command::NextCursorWall,
+                        _ => continue,
+                    },
+                    _ => continue,
+                },
+                Action::Input(input) => match input {
+                    InputEvent::Button { id: 0, event: button::Event::Pressed } => {
+                        Command::NextBgRainbow
+                    }
+                    // A long press on button 1 fires the trigger from `TRIGGER_ORIGIN` alone,
+                    // rather than after a plain trigger from its press.
+                    InputEvent::Button { id: 1, event: button::Event::Click } => Command::Trigger,
+                    InputEvent::Button { id: 1, event: button::Event::LongPress } => {
+                        Command::TriggerAt(TRIGGER_ORIGIN)
+                    }
+                    // Likewise, a long press on button 2 only switches the animation mode.
+                    InputEvent::Button { id: 2, event: button::Event::Click } => {
+                        Command::NextBgDuration
+                    }
+                    InputEvent::Button { id: 2, event: button::Event::LongPress } => {
+                        Command::NextAnimationMode
+                    }
+                    _ => continue,
+                },
+            };
+            commands.send(command).await;
+        }
+    }
+}
 
-    const STRIP_BRIGHTNESS: u8 = 255;
+// Prints where the calibration cursor is, to match the LED blinking on the wall to the layout.
+fn print_cursor(calibration: &Calibration) {
+    let info = calibration.cursor_info(&LAYOUT, &OUTPUTS);
+    let wall = WALL_NAMES[info.wall];
+    match info.physical {
+        Some((output, led)) => println!(
+            "Cursor: logical LED {}, {} wall pixel {}, output {} LED {}",
+            info.logical, wall, info.wall_pixel, output, led
+        ),
+        None => println!(
+            "Cursor: logical LED {}, {} wall pixel {}, missing from its strip",
+            info.logical, wall, info.wall_pixel
+        ),
+    }
+}
+
+// The main task doubles as the render task: it owns the LightingController and the
+// LogicalStrip, applies queued commands at the start of each frame, and renders frames when the
+// FrameScheduler says they are due.
+#[esp_rtos::main]
+async fn main(spawner: Spawner) -> ! {
+    const CROSSFADE_DURATION_US: u64 = 1_500_000;
+    const CROSSFADE_EASING: Easing = Easing::EaseInOut;
 
     let r_trig = [BLACK];
 
@@ -55,42 +201,78 @@ async fn main(_spawner: Spawner) -> ! {
     lc::utility::set_random_seed(rng.random().into()); //set random seed using hardware peripheral
 
     let frequency = Rate::from_mhz(80);
-    let rmt = Rmt::new(peripherals.RMT, frequency).expect("Failed to initialize RMT0");
+    let rmt = Rmt::new(peripherals.RMT, frequency)
+        .expect("Failed to initialize RMT0")
+        .into_async();
 
     //TODO: I want to next add a wifi peripheral and get it to pull an IP over DHCP to see if I can get that far.
-    
+
     // Setup GPIO Pins for buttons:
-    const BUTTON_DEBOUNCE_TIME: Duration = Duration::from_millis(20);
     let button_config = InputConfig::default().with_pull(esp_hal::gpio::Pull::Up);
 
-    let button_0 = Input::new(peripherals.GPIO0, button_config);
-    let button_1 = Input::new(peripherals.GPIO1, button_config);
-    let button_2 = Input::new(peripherals.GPIO2, button_config);
-
-    let mut last_button_0_level = button_0.level();
-    let mut last_button_1_level = button_1.level();
-    let mut last_button_2_level = button_2.level();
-
-    let mut last_button_0_sample_time = Instant::now();
-    let mut last_button_1_sample_time = Instant::now();
-    let mut last_button_2_sample_time = Instant::now();
-
-    let mut led_strip_1_buffer = smart_led_buffer!(NUM_LEDS_STRIP_CLOSET_WINDOW);
-    let mut led_strip_2_buffer = smart_led_buffer!(NUM_LEDS_STRIP_DOOR_NORTH);
-
-    // This strip has CLOSET_WALL and WINDOW_WALL
-    let mut led_strip_1 =
-        SmartLedsAdapter::new(rmt.channel0, peripherals.GPIO6, &mut led_strip_1_buffer);
-
-    // This strip has DOOR_WALL and NORTH_WALL
-    let mut led_strip_2 =
-        SmartLedsAdapter::new(rmt.channel1, peripherals.GPIO5, &mut led_strip_2_buffer);
-
-    let frame_rate = embedded_time::rate::Extensions::Hz(60);
-    let frame_rate_in_ticks = Duration::from_micros(16_667_u64);
-
-    let color_buffer = &mut [BLACK; NUM_LEDS];
-    let mut ls = LogicalStrip::new(color_buffer);
+    let button_0 = Button::new(Input::new(peripherals.GPIO0, button_config), BUTTON_DEFAULT);
+    let button_1 = Button::new(Input::new(peripherals.GPIO1, button_config), BUTTON_DEFAULT);
+    let button_2 = Button::new(Input::new(peripherals.GPIO2, button_config), BUTTON_DEFAULT);
+
+    spawner
+        .spawn(button_task(0, button_0, INPUT_EVENTS.sender()))
+        .expect("Failed to spawn button 0 task");
+    spawner
+        .spawn(button_task(1, button_1, INPUT_EVENTS.sender()))
+        .expect("Failed to spawn button 1 task");
+    spawner
+        .spawn(button_task(2, button_2, INPUT_EVENTS.sender()))
+        .expect("Failed to spawn button 2 task");
+    spawner
+        .spawn(control_task(INPUT_EVENTS.receiver(), COMMANDS.sender()))
+        .expect("Failed to spawn control task");
+
+    // The RMT channel, GPIO pin and adapter buffer of each entry in OUTPUTS, as listed in the
+    // layout file, and the LED adapter set up on each of them.
+    room_outputs!(led_outputs, led_strips, rmt, peripherals);
+    let mut write_health = [WriteHealth::default(); NUM_OUTPUTS];
+
+    // Relay or MOSFET switching the LED supplies, on the pin the layout file gives it. Starts off,
+    // the first non-black frame turns it on.
+    let mut psu = PsuControl::new(PSU_CONTROL);
+    let mut psu_pin = Output::new(
+        room_psu_pin!(peripherals),
+        Level::from(psu.pin_level()),
+        OutputConfig::default(),
+    );
+
+    let mut frame_scheduler = FrameScheduler::new(FRAME_RATE_HZ);
+    let frame_rate = frame_scheduler.frame_rate();
+
+    // Strips showing the same colors as last frame aren't resent, except once a second to
+    // recover from any glitches picked up on the data line.
+    let mut strip_refresh = [StripRefresh::new(frame_rate.0); NUM_OUTPUTS];
+
+    // Print timing stats roughly every 5 seconds when the telemetry feature is on. Without it the
+    // stages aren't timed at all.
+    #[cfg(feature = "telemetry")]
+    let mut telemetry = Telemetry::new(frame_rate.0 * 5);
+
+    // One color buffer for each of the two controllers, and one for the blend of the two while
+    // crossfading between them.
+    let color_buffer_a = &mut [BLACK; NUM_LEDS];
+    let color_buffer_b = &mut [BLACK; NUM_LEDS];
+    let blend_buffer = &mut [BLACK; NUM_LEDS];
+    // Double buffered output: the frame rendered on one pass of the loop is sent to the strips
+    // on the next pass, while the frame after it renders into the back buffer.
+    let mut front_buffer = &mut [BLACK; NUM_LEDS];
+    let mut back_buffer = &mut [BLACK; NUM_LEDS];
+    let mut wall_corrections = WALL_CORRECTIONS;
+    let mut dither = TemporalDither::<NUM_LEDS>::new();
+    #[cfg(feature = "linear_pipeline")]
+    let mut linear_buffer = LinearBuffer::<NUM_LEDS>::new();
+    // The incoming scene during a crossfade, blended into `linear_buffer`.
+    #[cfg(feature = "linear_pipeline")]
+    let mut fade_buffer = LinearBuffer::<NUM_LEDS>::new();
+    let mut strips = [
+        LogicalStrip::new(color_buffer_a),
+        LogicalStrip::new(color_buffer_b),
+    ];
 
     let mut bg_durations = [
         20_000_000_000,
@@ -105,201 +287,355 @@ async fn main(_spawner: Spawner) -> ! {
     .iter()
     .cycle()
     .copied();
-    
-    let mut rainbow_iter = rainbows.iter().cycle().copied();
-    #[cfg(feature = "office_lights")]
-    let (mut a1, mut a2, mut a3, mut a4) = {
-        let initial_rainbow = rainbow_iter.nth(3).expect("Iterates forever.");
-        // closet wall
-        let a1 = Animation::<NUM_LEDS_CLOSET_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(default_translation_array(START_CLOSET_INDEX))
-            // .set_bg_rainbow(&[RED, DARK_RED], true) //debug colors different for each wall
-            .set_bg_rainbow(initial_rainbow, RainbowDir::Forward)
-            .set_bg_duration_ns(20_000_000_000, frame_rate)
-            .set_bg_subdivisions(2)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        // window wall
-        let a2 = Animation::<NUM_LEDS_WINDOW_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(default_translation_array(START_WINDOW_INDEX))
-            // .set_bg_rainbow(&[BLUE, BLUE_VIOLET], true) //debug colors different for each wall
-            .set_bg_rainbow(initial_rainbow, RainbowDir::Forward)
-            .set_bg_duration_ns(20_000_000_000, frame_rate)
-            .set_bg_subdivisions(2)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        // door wall
-        let a3 = Animation::<NUM_LEDS_DOOR_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(core::array::from_fn(|i| (START_NORTH_INDEX - 1) - i))
-            // .set_bg_rainbow(&[YELLOW, ORANGE], true) //debug colors different for each wall
-            .set_bg_rainbow(initial_rainbow, RainbowDir::Forward)
-            .set_bg_duration_ns(20_000_000_000, frame_rate)
-            .set_bg_subdivisions(2)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        // north wall
-        let a4 = Animation::<NUM_LEDS_NORTH_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(core::array::from_fn(|i| (NUM_LEDS) - 1 - i))
-            // .set_bg_rainbow(&[GREEN, DARK_GREEN], true) //debug colors different for each wall
-            .set_bg_rainbow(initial_rainbow, RainbowDir::Forward)
-            .set_bg_duration_ns(20_000_000_000, frame_rate)
-            .set_bg_subdivisions(2)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        (a1, a2, a3, a4)
-    };
-
-    #[cfg(feature = "test_strip")]
-    let (mut a1, mut a2, mut a3, mut a4) = {
-        // closet wall
-        let a1 = Animation::<NUM_LEDS_CLOSET_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(default_translation_array(START_CLOSET_INDEX))
-            // .set_bg_rainbow(&[RED, DARK_RED], true) //debug colors different for each wall
-            .set_bg_rainbow(rainbows[3], RainbowDir::Forward)
-            .set_bg_duration_ns(3_000_000_000, frame_rate)
-            .set_bg_subdivisions(1)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        // window wall
-        let a2 = Animation::<NUM_LEDS_WINDOW_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(core::array::from_fn(|i| (START_DOOR_INDEX - 1) - i))
-            // .set_bg_rainbow(&[BLUE, BLUE_VIOLET], true) //debug colors different for each wall
-            .set_bg_rainbow(rainbows[2], RainbowDir::Forward)
-            .set_bg_duration_ns(3_000_000_000, frame_rate)
-            .set_bg_subdivisions(1)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        // door wall
-        let a3 = Animation::<NUM_LEDS_DOOR_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(default_translation_array(START_DOOR_INDEX))
-            // .set_bg_rainbow(&[YELLOW, ORANGE], true) //debug colors different for each wall
-            .set_bg_rainbow(rainbows[1], RainbowDir::Forward)
-            .set_bg_duration_ns(3_000_000_000, frame_rate)
-            .set_bg_subdivisions(1)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        // north wall
-        let a4 = Animation::<NUM_LEDS_NORTH_WALL>::new(ANI_DEFAULT, frame_rate)
-            .set_translation_array(core::array::from_fn(|i| (NUM_LEDS) - 1 - i))
-            // .set_bg_rainbow(&[GREEN, DARK_GREEN], true) //debug colors different for each wall
-            .set_bg_rainbow(rainbows[0], RainbowDir::Forward)
-            .set_bg_duration_ns(3_000_000_000, frame_rate)
-            .set_bg_subdivisions(1)
-            .set_trig_duration_ns(5_000_000_000, frame_rate)
-            .set_trig_fade_rainbow(&r_trig, RainbowDir::Forward)
-            .set_trig_incremental_rainbow(&r_trig, RainbowDir::Forward);
-
-        (a1, a2, a3, a4)
-    };
-
-    let animations: [&mut dyn Animatable; _] = [&mut a1, &mut a2, &mut a3, &mut a4];
-    let mut lc = LightingController::new(animations, frame_rate);
 
-    println!("Peripherals configured, entering main loop.");
+    // The next palette button moves on from the palette the room starts on.
+    let mut rainbow_iter = rainbows.iter().cycle().copied().skip(BG_RAINBOW + 1);
+
+    // Two copies of the animations, one per wall as described in the room's layout file, and two
+    // of the single animation running around the whole room. Only one copy is shown normally,
+    // the other renders the incoming scene alongside it while crossfading, then takes over.
+    room_animations!(wall_animations_a, frame_rate, rainbows, &r_trig);
+    room_animations!(wall_animations_b, frame_rate, rainbows, &r_trig);
+    room_loop_animation!(loop_animation_a, frame_rate, rainbows, &r_trig);
+    room_loop_animation!(loop_animation_b, frame_rate, rainbows, &r_trig);
+    let mut wall_controllers = [
+        LightingController::new(wall_animations_a, frame_rate),
+        LightingController::new(wall_animations_b, frame_rate),
+    ];
+    let mut loop_controllers = [
+        LightingController::new(loop_animation_a, frame_rate),
+        LightingController::new(loop_animation_b, frame_rate),
+    ];
+    // Which of the two controllers each copy is showing.
+    let mut modes = [AnimationMode::PerWall; 2];
+    let mut active = 0;
+    let mut crossfade: Option<Crossfade> = None;
+    // Background changes made since startup. The idle copy gets brought up to date with these
+    // before it fades in.
+    let mut bg_rainbow = None;
+    let mut bg_duration_ns = None;
+    // The background duration each copy was last brought up to date with, if it was changed.
+    let mut bg_durations_ns = [None; 2];
+    let mut mode = AnimationMode::PerWall;
+    // Replaces the animations while wiring up a room, see `calibration`.
+    let mut calibration: Option<Calibration> = None;
 
-    let mut last_update_time = Instant::now();
+    println!("Peripherals configured, entering main loop.");
 
     loop {
-        // Button 0 Updates:
-        if Instant::now() > (last_button_0_sample_time + BUTTON_DEBOUNCE_TIME) {
-            let current_button_0_level = button_0.level();
-            if (current_button_0_level == Level::Low) && (last_button_0_level == Level::High) {
-                println!("New Rainbow!");
-                let next_rainbow = rainbow_iter.next().expect("Iterates forever.");
-                lc.animations[0].update_bg_rainbow(next_rainbow, RainbowDir::Forward);
-                lc.animations[1].update_bg_rainbow(next_rainbow, RainbowDir::Forward);
-                lc.animations[2].update_bg_rainbow(next_rainbow, RainbowDir::Forward);
-                lc.animations[3].update_bg_rainbow(next_rainbow, RainbowDir::Forward);
+        let frame_start_us = now_us();
+        frame_scheduler.start_frame(frame_start_us);
+
+        if crossfade.is_some_and(|fade| fade.is_finished(frame_start_us)) {
+            active = 1 - active;
+            crossfade = None;
+        }
+
+        let mut scene_changed = false;
+        while let Ok(command) = COMMANDS.try_receive() {
+            match command {
+                Command::NextBgRainbow | Command::SetBgRainbow(_) => {
+                    let next_rainbow = match command {
+                        Command::SetBgRainbow(rainbow) => rainbow,
+                        _ => rainbow_iter.next().expect("Iterates forever."),
+                    };
+                    println!("New Rainbow!");
+                    bg_rainbow = Some(next_rainbow);
+                    scene_changed = true;
+                }
+
+                Command::NextBgDuration | Command::SetBgDurationNs(_) => {
+                    let dur = match command {
+                        Command::SetBgDurationNs(dur) => dur,
+                        _ => bg_durations.next().expect("Iterates forever."),
+                    };
+                    println!("New Duration: {: >2}s", dur / 1_000_000_000);
+                    bg_duration_ns = Some(dur);
+                    scene_changed = true;
+                }
+
+                Command::SetScene(scene) => {
+                    println!("New Scene!");
+                    bg_rainbow = Some(scene.bg_rainbow);
+                    bg_duration_ns = Some(scene.bg_duration_ns);
+                    scene_changed = true;
+                }
+
+                Command::NextAnimationMode | Command::SetAnimationMode(_) => {
+                    mode = match command {
+                        Command::SetAnimationMode(mode) => mode,
+                        _ => mode.next(),
+                    };
+                    println!("New Animation Mode: {:?}", mode);
+                    scene_changed = true;
+                }
+
+                Command::Trigger | Command::TriggerAt(_) => {
+                    let rand_num: u16 = (rng.random() & 0xFFFF) as u16;
+                    println!("Random Number Trigger Point: {:X}", rand_num);
+                    // Triggers at a point start each animation's effect from its LED nearest the
+                    // point.
+                    let starting_offset = |translation: &[usize]| match command {
+                        Command::TriggerAt(point) => SPATIAL_MAP.nearest_offset(point, translation),
+                        _ => 0,
+                    };
+                    let tp = |translation| animations::trigger::Parameters {
+                        mode: animations::trigger::Mode::ColorShotFade,
+                        direction: animations::Direction::Positive,
+                        fade_in_time_ns: 250_000_000_u64,
+                        fade_out_time_ns: 1_000_000_000_u64,
+                        starting_offset: starting_offset(translation),
+                        pixels_per_pixel_group: 1,
+                    };
+                    let running = if crossfade.is_some() { 2 } else { 1 };
+                    for slot in [active, 1 - active].into_iter().take(running) {
+                        match modes[slot] {
+                            AnimationMode::PerWall => {
+                                for wall in 0..NUM_WALLS {
+                                    wall_controllers[slot].trigger(wall, &tp(TRANSLATIONS[wall]));
+                                }
+                            }
+                            AnimationMode::WholeRoom => {
+                                loop_controllers[slot].trigger(0, &tp(&LOOP_TRANSLATION));
+                            }
+                        }
+                    }
+                }
+
+                Command::Calibrate(on) => {
+                    println!("Calibration {}", if on { "on" } else { "off" });
+                    calibration = on.then(Calibration::new);
+                    if let Some(calibration) = &calibration {
+                        print_cursor(calibration);
+                    }
+                }
+
+                Command::StepCursor(steps) => {
+                    if let Some(calibration) = calibration.as_mut() {
+                        calibration.step(steps, NUM_LEDS);
+                        print_cursor(calibration);
+                    }
+                }
+
+                Command::NextCursorWall => {
+                    if let Some(calibration) = calibration.as_mut() {
+                        calibration.next_wall(&LAYOUT);
+                        print_cursor(calibration);
+                    }
+                }
+
+                Command::SetCorrection { zone, correction } => {
+                    if let Some(wall) = wall_corrections.get_mut(zone) {
+                        wall.correction = correction;
+                    }
+                }
             }
-            last_button_0_level = current_button_0_level;
-            last_button_0_sample_time = Instant::now();
         }
-        {}.await();
-
-        // Button 1 Updates:
-        if Instant::now() > (last_button_1_sample_time + BUTTON_DEBOUNCE_TIME) {
-            let current_button_1_level = button_1.level();
-            if (current_button_1_level == Level::Low) && (last_button_1_level == Level::High) {
-                let rand_num: u16 = (rng.random() & 0xFFFF) as u16;
-                println!("Random Number Trigger Point: {:X}", rand_num);
-                let tp = animations::trigger::Parameters {
-                    mode: animations::trigger::Mode::ColorShotFade,
-                    direction: animations::Direction::Positive,
-                    fade_in_time_ns: 250_000_000_u64,
-                    fade_out_time_ns: 1_000_000_000_u64,
-                    starting_offset: 0,
-                    pixels_per_pixel_group: 1,
-                };
-                lc.trigger(0, &tp);
-                lc.trigger(1, &tp);
-                lc.trigger(2, &tp);
-                lc.trigger(3, &tp);
+
+        // Changes go to the idle controller, which is then faded in over the active one. Further
+        // changes during a crossfade go to the controller already fading in.
+        if scene_changed {
+            let incoming = 1 - active;
+            modes[incoming] = mode;
+            bg_durations_ns[incoming] = bg_duration_ns;
+            let animations = wall_controllers[incoming].animations.iter_mut();
+            for animation in animations.chain(loop_controllers[incoming].animations.iter_mut()) {
+                if let Some(rainbow) = bg_rainbow {
+                    animation.update_bg_rainbow(rainbow, RainbowDir::Forward);
+                }
+                if let Some(dur) = bg_duration_ns {
+                    animation.update_bg_duration_ns(dur, frame_rate);
+                }
             }
-            last_button_1_level = current_button_1_level;
-            last_button_1_sample_time = Instant::now();
+            if crossfade.is_none() {
+                crossfade = Some(Crossfade::new(
+                    frame_start_us,
+                    CROSSFADE_DURATION_US,
+                    CROSSFADE_EASING,
+                ));
+            }
+        }
+
+        // Both strips are started together and go out on the wire while the next frame renders,
+        // so the frame time is bounded by the longest strip or the render, whichever is slower.
+        let front: &[RGB8] = front_buffer;
+
+        // Nothing is sent while the supply is off or still settling, and every strip gets a full
+        // frame once it is back on.
+        let front_is_black = front.iter().all(|&color| color == BLACK);
+        let psu_ready = psu.update(frame_start_us, front_is_black);
+        psu_pin.set_level(Level::from(psu.pin_level()));
+        if !psu_ready {
+            strip_refresh.iter_mut().for_each(StripRefresh::invalidate);
         }
-        {}.await();
-
-        // Button 2 Updates:
-        if Instant::now() > (last_button_2_sample_time + BUTTON_DEBOUNCE_TIME) {
-            let current_button_2_level = button_2.level();
-            if (current_button_2_level == Level::Low) && (last_button_2_level == Level::High) {
-                println!("Press 2!");
-                let dur = bg_durations.next().expect("Iterates forever.");
-                println!("New Duration: {: >2}s", dur / 1_000_000_000);
-                lc.animations[0].update_bg_duration_ns(dur, frame_rate);
-                lc.animations[1].update_bg_duration_ns(dur, frame_rate);
-                lc.animations[2].update_bg_duration_ns(dur, frame_rate);
-                lc.animations[3].update_bg_duration_ns(dur, frame_rate);
+
+        // When each stage of the frame finished, for telemetry.
+        #[cfg(feature = "telemetry")]
+        let (mut update_done_us, mut color_done_us, mut transmit_done_us) = (0, 0, 0);
+        let send: [bool; NUM_OUTPUTS] = core::array::from_fn(|i| {
+            let hash = hash_colors(OUTPUTS.outputs[i].colors(front));
+            psu_ready && strip_refresh[i].should_send(hash)
+        });
+        let transmit = async {
+            let results = room_write_outputs!(led_strips, front, send);
+            #[cfg(feature = "telemetry")]
+            {
+                transmit_done_us = now_us();
+            }
+            results
+        };
+        let render = async {
+            // Lighting Updates:
+            let frame: &[RGB8] = if let Some(calibration) = &calibration {
+                // Calibration replaces the animations until it is switched off again.
+                calibration.render(&LAYOUT, &mut blend_buffer[..], frame_start_us);
+                &blend_buffer[..]
+            } else {
+                // Only the shown copy runs, and the incoming one alongside it while fading. The
+                // backgrounds are set from the clock before each update rather than left to count
+                // frames, so a copy that sat idle starts its fade exactly in step with the other.
+                let incoming = 1 - active;
+                let running = if crossfade.is_some() { 2 } else { 1 };
+                for slot in [active, incoming].into_iter().take(running) {
+                    let bg_offset = |duration_ns: u64| {
+                        let duration_ns = bg_durations_ns[slot].unwrap_or(duration_ns);
+                        background_offset(frame_start_us, duration_ns)
+                    };
+                    match modes[slot] {
+                        AnimationMode::PerWall => {
+                            let walls = wall_controllers[slot].animations.iter_mut();
+                            for (animation, &duration_ns) in walls.zip(&WALL_BG_DURATIONS_NS) {
+                                animation
+                                    .set_offset(AnimationType::Background, bg_offset(duration_ns));
+                            }
+                            wall_controllers[slot].update(&mut strips[slot]);
+                        }
+                        AnimationMode::WholeRoom => {
+                            for animation in loop_controllers[slot].animations.iter_mut() {
+                                animation.set_offset(
+                                    AnimationType::Background,
+                                    bg_offset(LOOP_BG_DURATION_NS),
+                                );
+                            }
+                            loop_controllers[slot].update(&mut strips[slot]);
+                        }
+                    }
+                }
+                match crossfade {
+                    // The linear pipeline blends the two after decoding them, in linear light.
+                    Some(fade) if cfg!(not(feature = "linear_pipeline")) => {
+                        blend(
+                            &strips[active].color_buffer[..],
+                            &strips[incoming].color_buffer[..],
+                            fade.amount(frame_start_us),
+                            &mut blend_buffer[..],
+                        );
+                        &blend_buffer[..]
+                    }
+                    _ => &strips[active].color_buffer[..],
+                }
+            };
+            #[cfg(feature = "telemetry")]
+            {
+                update_done_us = now_us();
+            }
+
+            #[cfg(not(feature = "linear_pipeline"))]
+            let supply_estimates = {
+                apply_luts(&OUTPUTS, &OUTPUT_GAMMA, frame, &mut back_buffer[..]);
+                apply_corrections(&wall_corrections, &mut back_buffer[..]);
+                let supply_estimates = POWER_LIMITER.limit(&OUTPUTS, &mut back_buffer[..]);
+                // Dithering has to be the last step, so the dithered outputs are redone at full
+                // precision with the corrections and power limit worked out above.
+                dither_outputs(
+                    &OUTPUTS,
+                    &OUTPUT_GAMMA,
+                    &wall_corrections,
+                    &POWER_LIMITER,
+                    &supply_estimates,
+                    frame,
+                    &mut back_buffer[..],
+                    &mut dither,
+                );
+                supply_estimates
+            };
+            #[cfg(feature = "linear_pipeline")]
+            let supply_estimates = {
+                linear_buffer.decode(&OUTPUTS, &OUTPUT_GAMMA, frame);
+                if let (Some(fade), None) = (crossfade, &calibration) {
+                    let incoming = &strips[1 - active].color_buffer[..];
+                    fade_buffer.decode(&OUTPUTS, &OUTPUT_GAMMA, incoming);
+                    linear_buffer.crossfade(&fade_buffer.colors, fade.amount(frame_start_us));
+                }
+                linear_buffer.apply_corrections(&wall_corrections);
+                let supply_estimates = POWER_LIMITER.limit_linear(&OUTPUTS, &mut linear_buffer);
+                linear_buffer.quantize(&OUTPUTS, &mut back_buffer[..], &mut dither);
+                supply_estimates
+            };
+            #[cfg(feature = "telemetry")]
+            {
+                color_done_us = now_us();
+            }
+            supply_estimates
+        };
+        #[cfg_attr(not(feature = "telemetry"), allow(unused_variables))]
+        let (results, supply_estimates) = join(transmit, render).await;
+        // A failed strip just misses this frame and gets the next one. Only the first failure in a
+        // row is logged, and a strip that keeps failing gets its adapter set up again.
+        let mut reinit = [false; NUM_OUTPUTS];
+        for (i, result) in results.into_iter().enumerate() {
+            let result = result.map_err(|error| OutputError::new(i, error));
+            #[cfg(feature = "telemetry")]
+            if !send[i] {
+                telemetry.skipped_writes += 1;
+            }
+            if result.is_err() {
+                #[cfg(feature = "telemetry")]
+                {
+                    telemetry.write_failures += 1;
+                }
+                strip_refresh[i].invalidate();
+            }
+            match (write_health[i].record(&result), &result) {
+                (WriteAction::Report, Err(error)) => println!("{}, retrying next frame", error),
+                (WriteAction::Reinit, _) => {
+                    println!("Output {} keeps failing, setting its adapter up again", i);
+                    #[cfg(feature = "telemetry")]
+                    {
+                        telemetry.reinits += 1;
+                    }
+                    reinit[i] = true;
+                }
+                _ => {}
             }
-            last_button_2_level = current_button_2_level;
-            last_button_2_sample_time = Instant::now();
         }
-        {}.await();
-
-        // Lighting Updates:
-        if Instant::now() > (last_update_time + frame_rate_in_ticks) {
-            last_update_time = Instant::now();
-            lc.update(&mut ls);
-
-            led_strip_1
-                .write(brightness(
-                    gamma(
-                        ls.color_buffer
-                            .iter()
-                            .take(NUM_LEDS_STRIP_CLOSET_WINDOW)
-                            .copied(),
-                    ),
-                    STRIP_BRIGHTNESS,
-                ))
-                .unwrap();
-
-            led_strip_2
-                .write(brightness(
-                    gamma(
-                        ls.color_buffer
-                            .iter()
-                            .skip(NUM_LEDS_STRIP_CLOSET_WINDOW)
-                            .copied(),
-                    ),
-                    STRIP_BRIGHTNESS,
-                ))
-                .unwrap();
+        if reinit.contains(&true) {
+            room_reinit_outputs!(led_strips, led_outputs, reinit);
         }
-        {}.await();
+        core::mem::swap(&mut front_buffer, &mut back_buffer);
+        frame_scheduler.end_frame(now_us());
+
+        #[cfg(feature = "telemetry")]
+        if telemetry.record_frame(
+            update_done_us - frame_start_us,
+            color_done_us - update_done_us,
+            transmit_done_us - frame_start_us,
+        ) {
+            telemetry.dropped_frames = frame_scheduler.stats().missed_deadlines;
+            println!("{}", telemetry);
+            for (i, estimate) in supply_estimates.iter().enumerate() {
+                println!(
+                    "supply {}: {}mA requested, {}mA delivered",
+                    i, estimate.requested_ma, estimate.delivered_ma
+                );
+            }
+            telemetry.reset();
+            frame_scheduler.reset_stats();
+        }
+
+        Timer::at(embassy_time::Instant::from_micros(
+            frame_scheduler.next_frame_due_us(),
+        ))
+        .await;
     }
 }
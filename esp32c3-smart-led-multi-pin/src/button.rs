@@ -0,0 +1,207 @@
+//! Debounced push button state machine.
+//!
+//! A `Button` wraps anything that can report a pin level and turns the raw samples into typed
+//! events. All timing is passed in by the caller as a microsecond timestamp, so the same state
+//! machine runs against `esp_hal::time::Instant` on the board or a scripted clock on the host.
+
+/// Anything that can report the current level of a button pin.
+pub trait LevelSource {
+    /// Returns `true` if the pin is currently reading high.
+    fn is_high(&mut self) -> bool;
+}
+
+impl<F: FnMut() -> bool> LevelSource for F {
+    fn is_high(&mut self) -> bool {
+        self()
+    }
+}
+
+#[cfg(feature = "esp32c3")]
+impl LevelSource for esp_hal::gpio::Input<'_> {
+    fn is_high(&mut self) -> bool {
+        esp_hal::gpio::Input::is_high(self)
+    }
+}
+
+/// Events emitted by a `Button`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The button went down, after debouncing.
+    Pressed,
+    /// The button came back up, after debouncing.
+    Released,
+    /// The button was released before a `LongPress`. This is emitted right after the `Released`
+    /// event, so a button with a long press action can act on this instead of `Pressed` and not
+    /// do both.
+    Click,
+    /// The button has been held down for `Config::long_press_us`. Fires once per press.
+    LongPress,
+    /// Fires every `Config::repeat_us` after a `LongPress` for as long as the button is held.
+    Held,
+    /// A second press started within `Config::double_click_us` of the previous short click.
+    /// This is emitted right after the `Pressed` event for the second press.
+    DoubleClick,
+}
+
+/// Timing and polarity settings for a `Button`. All times are in microseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// How long a new level has to be stable before it is accepted.
+    pub debounce_us: u64,
+    /// How long the button must be held before `Event::LongPress` fires.
+    pub long_press_us: u64,
+    /// Interval between `Event::Held` repeats after a long press. 0 disables repeats.
+    pub repeat_us: u64,
+    /// Maximum time between releasing a click and starting the next press for a double click.
+    /// 0 disables double click detection.
+    pub double_click_us: u64,
+    /// Set when the pin reads low while the button is pressed, e.g. buttons to ground with a
+    /// pull-up enabled.
+    pub active_low: bool,
+}
+
+/// Settings matching the pulled-up buttons on GPIO0/1/2.
+pub const BUTTON_DEFAULT: Config = Config {
+    debounce_us: 20_000,
+    long_press_us: 800_000,
+    repeat_us: 200_000,
+    double_click_us: 300_000,
+    active_low: true,
+};
+
+pub struct Button<P: LevelSource> {
+    source: P,
+    config: Config,
+    pressed: bool,
+    raw_pressed: bool,
+    raw_changed_at: u64,
+    pressed_at: u64,
+    long_press_fired: bool,
+    next_held_at: u64,
+    // Set when the current press was consumed by a long press or a double click, so its release
+    // does not arm another double click.
+    click_consumed: bool,
+    // Set while a press that was already down at boot is still held. It has no start time, so it
+    // produces no long press or repeats.
+    held_since_boot: bool,
+    last_click_at: Option<u64>,
+    pending: Option<Event>,
+}
+
+impl<P: LevelSource> Button<P> {
+    /// Creates a new button. The current pin level is taken as the starting state, so a button
+    /// that is already held at boot reports nothing until it is released.
+    pub fn new(mut source: P, config: Config) -> Self {
+        let pressed = source.is_high() != config.active_low;
+        Button {
+            source,
+            config,
+            pressed,
+            raw_pressed: pressed,
+            raw_changed_at: 0,
+            pressed_at: 0,
+            long_press_fired: false,
+            next_held_at: 0,
+            click_consumed: pressed,
+            held_since_boot: pressed,
+            last_click_at: None,
+            pending: None,
+        }
+    }
+
+    /// Samples the pin and advances the state machine to `now_us`.
+    ///
+    /// A single sample can produce more than one event, so keep calling this with the same
+    /// timestamp until it returns `None`.
+    pub fn update(&mut self, now_us: u64) -> Option<Event> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+
+        let raw_pressed = self.source.is_high() != self.config.active_low;
+        if raw_pressed != self.raw_pressed {
+            self.raw_pressed = raw_pressed;
+            self.raw_changed_at = now_us;
+        }
+
+        if self.raw_pressed != self.pressed
+            && now_us.saturating_sub(self.raw_changed_at) >= self.config.debounce_us
+        {
+            self.pressed = self.raw_pressed;
+            return Some(if self.pressed {
+                self.press(now_us)
+            } else {
+                self.release(now_us)
+            });
+        }
+
+        if self.pressed && !self.held_since_boot {
+            if !self.long_press_fired
+                && now_us.saturating_sub(self.pressed_at) >= self.config.long_press_us
+            {
+                self.long_press_fired = true;
+                self.click_consumed = true;
+                self.next_held_at = now_us + self.config.repeat_us;
+                return Some(Event::LongPress);
+            }
+            if self.long_press_fired && self.config.repeat_us > 0 && now_us >= self.next_held_at {
+                self.next_held_at += self.config.repeat_us;
+                return Some(Event::Held);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the next time `update` needs to run even if the pin level does not change, or
+    /// `None` if the button is idle and only a level change can produce a new event.
+    pub fn next_deadline_us(&self) -> Option<u64> {
+        if self.pending.is_some() {
+            Some(0)
+        } else if self.raw_pressed != self.pressed {
+            Some(self.raw_changed_at + self.config.debounce_us)
+        } else if !self.pressed || self.held_since_boot {
+            None
+        } else if !self.long_press_fired {
+            Some(self.pressed_at + self.config.long_press_us)
+        } else if self.config.repeat_us > 0 {
+            Some(self.next_held_at)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the debounced state of the button.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Gives access to the wrapped level source, e.g. to wait for an edge on a GPIO input.
+    pub fn source_mut(&mut self) -> &mut P {
+        &mut self.source
+    }
+
+    fn press(&mut self, now_us: u64) -> Event {
+        self.pressed_at = now_us;
+        self.held_since_boot = false;
+        self.long_press_fired = false;
+        self.click_consumed = false;
+        if let Some(last_click_at) = self.last_click_at.take()
+            && now_us.saturating_sub(last_click_at) <= self.config.double_click_us
+        {
+            self.click_consumed = true;
+            self.pending = Some(Event::DoubleClick);
+        }
+        Event::Pressed
+    }
+
+    fn release(&mut self, now_us: u64) -> Event {
+        if !self.click_consumed && self.config.double_click_us > 0 {
+            self.last_click_at = Some(now_us);
+        }
+        if !self.long_press_fired && !self.held_since_boot {
+            self.pending = Some(Event::Click);
+        }
+        Event::Released
+    }
+}
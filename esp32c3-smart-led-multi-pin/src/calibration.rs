@@ -0,0 +1,116 @@
+//! Calibration mode, for checking a room's layout against the LEDs on its walls.
+//!
+//! While calibrating, the animations are replaced by a fixed pattern: every wall is lit dimly in
+//! its own color, the first LED of each wall's animation is white and the last is the wall's
+//! color at full brightness, so a wall that is too long, too short or running the wrong way
+//! shows up at a glance. A blinking cursor LED can be stepped along the logical buffer, and
+//! `cursor_info` says where it is on the walls and on the strips, so the numbers to put in the
+//! layout file can be read off without reflashing.
+
+use crate::layout::Layout;
+use crate::output::OutputMap;
+use rgb::RGB8;
+use smart_leds::colors::*;
+
+/// Colors of the walls, in layout order. Rooms with more walls than colors start over.
+pub const WALL_COLORS: [RGB8; 6] = [RED, BLUE, YELLOW, GREEN, MAGENTA, CYAN];
+/// How much the walls are dimmed by, as a right shift, so the markers and cursor stand out and
+/// the whole room lit at once stays well inside the supply budget.
+pub const WALL_DIM_SHIFT: u8 = 3;
+/// Color of the first LED of each wall's animation.
+pub const START_MARKER: RGB8 = WHITE;
+/// How long the cursor spends on and off while blinking.
+pub const CURSOR_BLINK_US: u64 = 250_000;
+
+/// Where the cursor is, in every numbering the layout uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CursorInfo {
+    /// Index in the logical buffer.
+    pub logical: usize,
+    /// The wall the cursor is on.
+    pub wall: usize,
+    /// Pixel of the wall's animation the cursor is on, 0 being the wall's start marker.
+    pub wall_pixel: usize,
+    /// The output showing the cursor and its LED's position on that strip, or `None` if the LED
+    /// is missing.
+    pub physical: Option<(usize, usize)>,
+}
+
+pub struct Calibration {
+    cursor: usize,
+}
+
+impl Calibration {
+    /// Starts calibrating with the cursor on the first LED of the logical buffer.
+    pub const fn new() -> Self {
+        Calibration { cursor: 0 }
+    }
+
+    /// Index in the logical buffer of the cursor.
+    pub const fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Moves the cursor `steps` LEDs along the logical buffer, wrapping around at either end.
+    pub fn step(&mut self, steps: isize, num_leds: usize) {
+        self.cursor = (self.cursor as isize + steps).rem_euclid(num_leds as isize) as usize;
+    }
+
+    /// Moves the cursor to the start marker of the wall after the one it is on.
+    pub fn next_wall<const W: usize, const S: usize>(&mut self, layout: &Layout<W, S>) {
+        let wall = (layout.wall_at(self.cursor) + 1) % W;
+        self.cursor = layout.animation_pixel(wall, 0);
+    }
+
+    /// Describes where the cursor is in `layout` and on the strips in `outputs`.
+    pub fn cursor_info<const W: usize, const S: usize>(
+        &self,
+        layout: &Layout<W, S>,
+        outputs: &OutputMap<S>,
+    ) -> CursorInfo {
+        let wall = layout.wall_at(self.cursor);
+        let offset = self.cursor - layout.start_index(wall);
+        let wall_pixel = if layout.walls[wall].reverse {
+            layout.wall_len(wall) - 1 - offset
+        } else {
+            offset
+        };
+        CursorInfo {
+            logical: self.cursor,
+            wall,
+            wall_pixel,
+            physical: outputs.physical_index(self.cursor),
+        }
+    }
+
+    /// Draws the calibration pattern for `layout` into `buffer`, a logical color buffer.
+    pub fn render<const W: usize, const S: usize>(
+        &self,
+        layout: &Layout<W, S>,
+        buffer: &mut [RGB8],
+        now_us: u64,
+    ) {
+        for wall in 0..W {
+            let color = WALL_COLORS[wall % WALL_COLORS.len()];
+            let start = layout.start_index(wall);
+            let len = layout.wall_len(wall);
+            for led in buffer[start..start + len].iter_mut() {
+                *led = RGB8 {
+                    r: color.r >> WALL_DIM_SHIFT,
+                    g: color.g >> WALL_DIM_SHIFT,
+                    b: color.b >> WALL_DIM_SHIFT,
+                };
+            }
+            buffer[layout.animation_pixel(wall, len - 1)] = color;
+            buffer[layout.animation_pixel(wall, 0)] = START_MARKER;
+        }
+        let cursor_on = (now_us / CURSOR_BLINK_US).is_multiple_of(2);
+        buffer[self.cursor] = if cursor_on { WHITE } else { BLACK };
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,116 @@
+//! Two button combos.
+//!
+//! Pressing two buttons together does something different from pressing either one on its own,
+//! so a press of either combo button is held back until it is clear it isn't the start of the
+//! combo: the button is released, or `window_us` passes without the other one going down. Once
+//! the combo fires, everything else from both buttons is dropped until each is pressed again, so
+//! their long presses, repeats and clicks don't act on their own either.
+//!
+//! Like a `Button`, a `Combo` is handed the time by the caller, so it runs the same on the board
+//! and in host tests.
+
+use crate::button::Event;
+use crate::input::InputEvent;
+
+/// What an input turned out to be, returned by `Combo::update`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// An input to handle as usual. Presses of the combo buttons arrive late, when it is clear
+    /// they aren't part of the combo.
+    Input(InputEvent),
+    /// Both combo buttons were pressed together.
+    Combo,
+}
+
+pub struct Combo {
+    buttons: [u8; 2],
+    window_us: u64,
+    // The held back press of one of the buttons, and the time it stops waiting for the other.
+    pending: Option<(u8, u64)>,
+    // Set for each button from the combo firing until the button is next pressed.
+    suppressed: [bool; 2],
+    // Actions not yet returned by `update`. One input leads to at most two.
+    actions: [Option<Action>; 2],
+}
+
+impl Combo {
+    /// Creates a combo of the buttons with ids `buttons`, which have to go down within
+    /// `window_us` of each other.
+    pub const fn new(buttons: [u8; 2], window_us: u64) -> Self {
+        Combo {
+            buttons,
+            window_us,
+            pending: None,
+            suppressed: [false; 2],
+            actions: [None; 2],
+        }
+    }
+
+    /// Records one input. Call `update` until it returns `None` before the next one.
+    pub fn input(&mut self, input: InputEvent, now_us: u64) {
+        self.expire(now_us);
+        let InputEvent::Button { id, event } = input;
+        let Some(button) = self.buttons.iter().position(|&b| b == id) else {
+            self.push(Action::Input(input));
+            return;
+        };
+        if self.suppressed[button] {
+            // The click that follows the release still belongs to the combo press, so it's the
+            // next press that ends it.
+            if event != Event::Pressed {
+                return;
+            }
+            self.suppressed[button] = false;
+        }
+        match (event, self.pending) {
+            (Event::Pressed, None) => self.pending = Some((id, now_us + self.window_us)),
+            (Event::Pressed, Some((pending_id, _))) if pending_id != id => {
+                self.pending = None;
+                self.suppressed = [true; 2];
+                self.push(Action::Combo);
+            }
+            _ => {
+                // Anything else from the held back button means it is on its own.
+                if self.pending.is_some_and(|(pending_id, _)| pending_id == id) {
+                    self.release_pending();
+                }
+                self.push(Action::Input(input));
+            }
+        }
+    }
+
+    /// Returns the next action to take, if any. Call it until it returns `None` after every
+    /// `input`, and again once `next_deadline_us` has passed.
+    pub fn update(&mut self, now_us: u64) -> Option<Action> {
+        self.expire(now_us);
+        let action = self.actions[0].take();
+        self.actions.rotate_left(1);
+        action
+    }
+
+    /// The time the held back press stops waiting for the other button, if there is one.
+    pub fn next_deadline_us(&self) -> Option<u64> {
+        self.pending.map(|(_, deadline_us)| deadline_us)
+    }
+
+    fn expire(&mut self, now_us: u64) {
+        if self
+            .pending
+            .is_some_and(|(_, deadline_us)| now_us >= deadline_us)
+        {
+            self.release_pending();
+        }
+    }
+
+    fn release_pending(&mut self) {
+        if let Some((id, _)) = self.pending.take() {
+            let event = Event::Pressed;
+            self.push(Action::Input(InputEvent::Button { id, event }));
+        }
+    }
+
+    fn push(&mut self, action: Action) {
+        let slot = self.actions.iter_mut().find(|slot| slot.is_none());
+        *slot.expect("Actions are taken after every input.") = Some(action);
+    }
+}
@@ -0,0 +1,81 @@
+//! Commands accepted by the render loop.
+//!
+//! The render loop owns the `LightingController` and is the only place animations get changed.
+//! Everything else (the control task today, a network task later) asks for changes by sending a
+//! `Command`. Commands are applied at the start of the next frame, in the order they were sent.
+
+use crate::correction::ColorCorrection;
+use crate::spatial::Point;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, Receiver, Sender};
+use rgb::RGB8;
+
+/// Number of commands that can be queued before senders have to wait for the next frame.
+pub const COMMAND_QUEUE_LEN: usize = 8;
+
+/// A complete background setup for every animation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scene {
+    pub bg_rainbow: &'static [RGB8],
+    pub bg_duration_ns: u64,
+}
+
+/// How the room is animated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// A separate animation on each wall, so palettes start over at every corner.
+    PerWall,
+    /// One animation running around the whole room as a continuous loop.
+    WholeRoom,
+}
+
+impl AnimationMode {
+    pub const fn next(&self) -> Self {
+        match self {
+            AnimationMode::PerWall => AnimationMode::WholeRoom,
+            AnimationMode::WholeRoom => AnimationMode::PerWall,
+        }
+    }
+}
+
+/// Palette, duration, mode and scene changes crossfade from the old look to the new one rather than
+/// switching on the next frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Switch every animation's background to the next palette in the render loop's rotation.
+    NextBgRainbow,
+    /// Switch every animation's background to a specific palette.
+    SetBgRainbow(&'static [RGB8]),
+    /// Switch every animation's background cycle to the next duration in the rotation.
+    NextBgDuration,
+    /// Set the background cycle duration of every animation, in nanoseconds.
+    SetBgDurationNs(u64),
+    /// Switch every animation to a new scene.
+    SetScene(Scene),
+    /// Switch between per-wall and whole room animation.
+    NextAnimationMode,
+    /// Set whether the room is animated per wall or as a whole.
+    SetAnimationMode(AnimationMode),
+    /// Fire the default trigger on every animation.
+    Trigger,
+    /// Fire the default trigger on every animation, starting from the LED nearest to the given
+    /// point in the room on each wall, or on the whole room loop.
+    TriggerAt(Point),
+    /// Switch calibration mode on or off. While it is on the animations are replaced by the
+    /// calibration pattern.
+    Calibrate(bool),
+    /// Move the calibration cursor this many LEDs along the logical buffer.
+    StepCursor(isize),
+    /// Move the calibration cursor to the start of the next wall.
+    NextCursorWall,
+    /// Replace the brightness and white balance correction of one zone of the layout's
+    /// `WALL_CORRECTIONS`.
+    SetCorrection {
+        zone: usize,
+        correction: ColorCorrection,
+    },
+}
+
+pub type CommandChannel = Channel<CriticalSectionRawMutex, Command, COMMAND_QUEUE_LEN>;
+pub type CommandSender = Sender<'static, CriticalSectionRawMutex, Command, COMMAND_QUEUE_LEN>;
+pub type CommandReceiver = Receiver<'static, CriticalSectionRawMutex, Command, COMMAND_QUEUE_LEN>;
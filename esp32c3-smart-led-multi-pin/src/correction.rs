@@ -0,0 +1,64 @@
+//! Per-wall brightness and white balance correction.
+//!
+//! Different strip reels have visibly different whites. Each `CorrectionZone` covers a slice of
+//! the logical buffer (usually one wall) and scales its colors after gamma correction, where the
+//! values are proportional to LED duty and a plain multiply is a true brightness change.
+
+use rgb::{RGB8, RGB16};
+
+/// Scale factors out of 255 applied to every LED in a zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorCorrection {
+    pub brightness: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+pub const NO_CORRECTION: ColorCorrection =
+    ColorCorrection { brightness: 255, r: 255, g: 255, b: 255 };
+
+impl ColorCorrection {
+    pub fn apply(&self, color: RGB8) -> RGB8 {
+        let scale = |channel: u8, factor: u8| {
+            let factor = factor as u32 * self.brightness as u32;
+            ((channel as u32 * factor + 255 * 255 / 2) / (255 * 255)) as u8
+        };
+        RGB8 {
+            r: scale(color.r, self.r),
+            g: scale(color.g, self.g),
+            b: scale(color.b, self.b),
+        }
+    }
+
+    /// Like `apply`, for the 16-bit colors of a `LinearBuffer`.
+    pub fn apply_fixed(&self, color: RGB16) -> RGB16 {
+        let scale = |channel: u16, factor: u8| {
+            let factor = factor as u32 * self.brightness as u32;
+            ((channel as u32 * factor + 255 * 255 / 2) / (255 * 255)) as u16
+        };
+        RGB16 {
+            r: scale(color.r, self.r),
+            g: scale(color.g, self.g),
+            b: scale(color.b, self.b),
+        }
+    }
+}
+
+/// A slice of the logical buffer that shares one correction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CorrectionZone {
+    pub start: usize,
+    pub len: usize,
+    pub correction: ColorCorrection,
+}
+
+/// Applies every zone's correction to its slice of `buffer`. LEDs not covered by any zone are
+/// left alone.
+pub fn apply_corrections(zones: &[CorrectionZone], buffer: &mut [RGB8]) {
+    for zone in zones.iter().filter(|zone| zone.correction != NO_CORRECTION) {
+        for color in buffer[zone.start..zone.start + zone.len].iter_mut() {
+            *color = zone.correction.apply(*color);
+        }
+    }
+}
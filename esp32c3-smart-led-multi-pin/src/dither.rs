@@ -0,0 +1,96 @@
+//! Temporal dithering.
+//!
+//! When a color sits between two 8-bit levels, `TemporalDither` alternates the LED between them
+//! from frame to frame so that the average over time lands on the exact target. This keeps slow,
+//! dim fades smooth instead of visibly stepping from one level to the next.
+//!
+//! Dithering has to be the last step before the colors are sent: anything that rounds the
+//! dithered colors again, like an 8-bit correction or power limit, would undo it. In the default
+//! pipeline the output stage is first run in 8 bits for every output, then `dither_outputs`
+//! redoes the dithered outputs in 8.8 fixed point with the same corrections and power limit, and
+//! only quantizes at the end.
+
+use crate::correction::{CorrectionZone, NO_CORRECTION};
+use crate::gamma::GammaLut;
+use crate::output::OutputMap;
+use crate::power::{PowerLimiter, SupplyEstimate};
+use rgb::{RGB8, RGB16};
+
+/// Per-LED quantization error carried over between frames.
+pub struct TemporalDither<const N: usize> {
+    residuals: [RGB8; N],
+}
+
+impl<const N: usize> TemporalDither<N> {
+    pub const fn new() -> Self {
+        TemporalDither { residuals: [RGB8 { r: 0, g: 0, b: 0 }; N] }
+    }
+
+    /// Quantizes an 8.8 fixed point color for the LED at `index` in the logical buffer, carrying
+    /// the leftover fraction over to that LED's next frame.
+    pub fn quantize(&mut self, index: usize, target: RGB16) -> RGB8 {
+        let residual = &mut self.residuals[index];
+        RGB8 {
+            r: quantize_channel(target.r, &mut residual.r),
+            g: quantize_channel(target.g, &mut residual.g),
+            b: quantize_channel(target.b, &mut residual.b),
+        }
+    }
+
+    /// Forgets all carried over error, e.g. after the output was blanked.
+    pub fn reset(&mut self) {
+        self.residuals = [RGB8 { r: 0, g: 0, b: 0 }; N];
+    }
+}
+
+impl<const N: usize> Default for TemporalDither<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Replaces the colors in `output_buffer` of every output with `dither` set. Each LED's color in
+/// `colors` goes through its output's table, its zone's correction and its supply's scale from
+/// `estimates`, all in 8.8 fixed point, and is then quantized through `dither`.
+#[allow(clippy::too_many_arguments)]
+pub fn dither_outputs<const N: usize, const S: usize, const M: usize>(
+    outputs: &OutputMap<N>,
+    luts: &[GammaLut; N],
+    zones: &[CorrectionZone],
+    limiter: &PowerLimiter<S>,
+    estimates: &[SupplyEstimate; S],
+    colors: &[RGB8],
+    output_buffer: &mut [RGB8],
+    dither: &mut TemporalDither<M>,
+) {
+    let dithered = outputs.outputs.iter().zip(luts).enumerate();
+    for (index, (output, lut)) in dithered.filter(|(_, (output, _))| output.dither) {
+        let scale = limiter.output_scale(estimates, index) as u32;
+        let limit = |channel: u16| (channel as u32 * scale / 256) as u16;
+        for i in output.start..output.start + output.len {
+            let correction = zones
+                .iter()
+                .find(|zone| (zone.start..zone.start + zone.len).contains(&i))
+                .map_or(NO_CORRECTION, |zone| zone.correction);
+            let color = colors[i];
+            let fixed = correction.apply_fixed(RGB16 {
+                r: lut.apply_fixed(color.r),
+                g: lut.apply_fixed(color.g),
+                b: lut.apply_fixed(color.b),
+            });
+            let limited = RGB16 { r: limit(fixed.r), g: limit(fixed.g), b: limit(fixed.b) };
+            output_buffer[i] = dither.quantize(i, limited);
+        }
+    }
+}
+
+fn quantize_channel(target: u16, residual: &mut u8) -> u8 {
+    let value = target as u32 + *residual as u32;
+    if value >= 255 << 8 {
+        // Already at full brightness, there is nothing left to carry.
+        *residual = 0;
+        return 255;
+    }
+    *residual = (value & 0xff) as u8;
+    (value >> 8) as u8
+}
@@ -0,0 +1,84 @@
+//! Strip write errors and the policy for recovering from them.
+//!
+//! A failed write only costs one frame on one strip, so the render loop keeps going: the error is
+//! counted and logged, and the strip simply gets the next frame. If a strip keeps failing, its
+//! `WriteHealth` asks for its adapter to be set up on its channel again.
+
+use core::fmt;
+
+/// An adapter error, tagged with the index in the `OutputMap` of the strip it came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputError<E> {
+    pub output: usize,
+    pub error: E,
+}
+
+impl<E> OutputError<E> {
+    pub const fn new(output: usize, error: E) -> Self {
+        OutputError { output, error }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for OutputError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "write to output {} failed: {:?}",
+            self.output, self.error
+        )
+    }
+}
+
+/// An error from either kind of strip adapter, so the results of every strip in a room fit in
+/// one array.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StripError<R, S> {
+    /// From the RMT adapter of a one-wire strip.
+    Rmt(R),
+    /// From the `Apa102Adapter` of an SPI strip.
+    Spi(S),
+}
+
+/// Failed writes in a row after which a strip's adapter gets set up again.
+pub const REINIT_AFTER_FAILURES: u32 = 10;
+
+/// What the render loop should do about a strip after recording a write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteAction {
+    /// Keep using the strip as is. Failed frames are retried by sending the next frame.
+    Continue,
+    /// Log the failure and keep going. Returned for the first failure after a successful write,
+    /// so a strip that keeps failing doesn't flood the serial port.
+    Report,
+    /// The strip has failed `REINIT_AFTER_FAILURES` writes in a row, set its adapter up again.
+    Reinit,
+}
+
+/// Tracks failed writes in a row for one strip. The total number of failures is counted by the
+/// telemetry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteHealth {
+    /// Failed writes since the last successful one.
+    pub consecutive_failures: u32,
+}
+
+impl WriteHealth {
+    /// Records the result of one write and returns what to do about it.
+    pub fn record<T, E>(&mut self, result: &Result<T, E>) -> WriteAction {
+        if result.is_ok() {
+            self.consecutive_failures = 0;
+            return WriteAction::Continue;
+        }
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= REINIT_AFTER_FAILURES {
+            // Start counting again, so a strip that is still failing is set up again every
+            // `REINIT_AFTER_FAILURES` frames.
+            self.consecutive_failures = 0;
+            WriteAction::Reinit
+        } else if self.consecutive_failures == 1 {
+            WriteAction::Report
+        } else {
+            WriteAction::Continue
+        }
+    }
+}
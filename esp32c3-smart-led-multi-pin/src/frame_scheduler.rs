@@ -0,0 +1,118 @@
+//! Frame timing.
+//!
+//! The firmware creates a single `FrameScheduler` from the layout's `FRAME_RATE_HZ`. Everything
+//! else (the `embedded_time` rate the lighting controller wants, the `esp_hal`/`embassy_time`
+//! durations the render loop waits on) is derived from it, so the two can't drift apart. It also
+//! keeps timing stats for the render loop. Timestamps are passed in as microseconds so the stats
+//! can be driven by a fake clock on the host.
+
+use embedded_time::rate::Hertz;
+
+pub struct FrameScheduler {
+    rate_hz: u32,
+    period_us: u64,
+    next_frame_due_us: Option<u64>,
+    frame_due_us: u64,
+    frame_started_us: u64,
+    stats: FrameStats,
+}
+
+/// Timing stats collected since the scheduler was created or `reset_stats` was last called.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of frames that have finished rendering.
+    pub frames: u32,
+    /// Number of frames that finished after the next frame was already due.
+    pub missed_deadlines: u32,
+    /// Largest difference between when a frame was due and when it actually started.
+    pub max_jitter_us: u64,
+    /// Longest time spent between `start_frame` and `end_frame`.
+    pub worst_render_us: u64,
+    /// Sum of all render times, for averaging.
+    pub total_render_us: u64,
+}
+
+impl FrameScheduler {
+    pub const fn new(rate_hz: u32) -> Self {
+        assert!(rate_hz > 0, "Frame rate must be at least 1Hz.");
+        FrameScheduler {
+            rate_hz,
+            period_us: (1_000_000 + rate_hz as u64 / 2) / rate_hz as u64,
+            next_frame_due_us: None,
+            frame_due_us: 0,
+            frame_started_us: 0,
+            stats: FrameStats {
+                frames: 0,
+                missed_deadlines: 0,
+                max_jitter_us: 0,
+                worst_render_us: 0,
+                total_render_us: 0,
+            },
+        }
+    }
+
+    pub fn from_hertz(rate: Hertz) -> Self {
+        Self::new(rate.0)
+    }
+
+    /// The frame rate in the form the lighting controller and animations expect.
+    pub fn frame_rate(&self) -> Hertz {
+        Hertz(self.rate_hz)
+    }
+
+    /// The frame period, rounded to the nearest microsecond.
+    pub const fn period_us(&self) -> u64 {
+        self.period_us
+    }
+
+    pub fn period(&self) -> embassy_time::Duration {
+        embassy_time::Duration::from_micros(self.period_us)
+    }
+
+    #[cfg(feature = "esp32c3")]
+    pub fn rate(&self) -> esp_hal::time::Rate {
+        esp_hal::time::Rate::from_hz(self.rate_hz)
+    }
+
+    #[cfg(feature = "esp32c3")]
+    pub fn hal_period(&self) -> esp_hal::time::Duration {
+        esp_hal::time::Duration::from_micros(self.period_us)
+    }
+
+    /// When the next frame should start. Wait on this rather than a free-running `Ticker`, so a
+    /// missed deadline doesn't turn into a burst of back-to-back catch-up frames.
+    pub fn next_frame_due_us(&self) -> u64 {
+        self.next_frame_due_us.unwrap_or(0)
+    }
+
+    /// Call when the render loop wakes up for a new frame.
+    pub fn start_frame(&mut self, now_us: u64) {
+        let due_us = self.next_frame_due_us.unwrap_or(now_us);
+        self.frame_due_us = due_us;
+        self.frame_started_us = now_us;
+        self.stats.max_jitter_us = self.stats.max_jitter_us.max(now_us.abs_diff(due_us));
+        self.next_frame_due_us = Some(due_us + self.period_us);
+    }
+
+    /// Call when the frame has been handed off to the strips.
+    pub fn end_frame(&mut self, now_us: u64) {
+        let render_us = now_us.saturating_sub(self.frame_started_us);
+        self.stats.frames += 1;
+        self.stats.total_render_us += render_us;
+        self.stats.worst_render_us = self.stats.worst_render_us.max(render_us);
+
+        if now_us > self.frame_due_us + self.period_us {
+            self.stats.missed_deadlines += 1;
+            // Don't try to catch up on frames we've already lost, just schedule from here.
+            self.next_frame_due_us = Some(now_us);
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = FrameStats::default();
+    }
+}
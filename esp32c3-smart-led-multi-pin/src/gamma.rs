@@ -0,0 +1,154 @@
+//! Gamma correction lookup tables.
+//!
+//! A `GammaLut` bakes a gamma curve and a brightness scale into a single 256 entry table, so the
+//! render loop does one table lookup per channel instead of running `gamma` and `brightness` as
+//! separate passes. Tables are built by `const fn`, so the curves for each strip type are
+//! computed at compile time. Each output's table comes from the `gamma` and `brightness` set for
+//! it in the layout file.
+//!
+//! Table entries are kept in 8.8 fixed point. The render loop normally just rounds them to 8 bits,
+//! but outputs with dithering enabled keep the fraction through the rest of the output stage and
+//! pass it on to `TemporalDither`.
+
+use crate::output::OutputMap;
+use rgb::RGB8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GammaLut {
+    gamma: f32,
+    brightness: u8,
+    table: [u16; 256],
+}
+
+/// Matches the fixed curve used by `smart_leds::gamma`.
+pub const GAMMA_2_8: GammaLut = GammaLut::new(2.8);
+/// A gentler curve that keeps more detail at the low end, useful for warm palettes.
+pub const GAMMA_2_2: GammaLut = GammaLut::new(2.2);
+/// No gamma correction.
+pub const GAMMA_LINEAR: GammaLut = GammaLut::new(1.0);
+
+impl GammaLut {
+    pub const fn new(gamma: f32) -> Self {
+        Self::with_brightness(gamma, 255)
+    }
+
+    /// Builds a table for `gamma` with every output additionally scaled by `brightness / 255`.
+    pub const fn with_brightness(gamma: f32, brightness: u8) -> Self {
+        assert!(gamma > 0.0, "Gamma must be positive.");
+        let mut table = [0; 256];
+        let mut i = 1;
+        while i < 256 {
+            let linear = powf(i as f64 / 255.0, gamma as f64);
+            // Truncated rather than rounded, so rounding the entry to 8 bits in `apply` gives
+            // the same level as rounding the curve itself.
+            table[i] = (linear * brightness as f64 * 256.0) as u16;
+            i += 1;
+        }
+        GammaLut { gamma, brightness, table }
+    }
+
+    pub const fn gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    pub const fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub const fn apply(&self, value: u8) -> u8 {
+        let fixed = self.table[value as usize] as u32;
+        let rounded = (fixed + 128) >> 8;
+        if rounded > 255 { 255 } else { rounded as u8 }
+    }
+
+    /// Like `apply`, but returns the full 8.8 fixed point value.
+    pub const fn apply_fixed(&self, value: u8) -> u16 {
+        self.table[value as usize]
+    }
+
+    pub const fn apply_color(&self, color: RGB8) -> RGB8 {
+        RGB8 {
+            r: self.apply(color.r),
+            g: self.apply(color.g),
+            b: self.apply(color.b),
+        }
+    }
+}
+
+/// Copies `colors` into `output_buffer`, running each output's slice through that output's table.
+/// Every output is rounded here, the ones with `dither` set are redone at full precision by
+/// `dither::dither_outputs` once the rest of the frame is finished.
+pub fn apply_luts<const N: usize>(
+    outputs: &OutputMap<N>,
+    luts: &[GammaLut; N],
+    colors: &[RGB8],
+    output_buffer: &mut [RGB8],
+) {
+    for (output, lut) in outputs.outputs.iter().zip(luts) {
+        for i in output.start..output.start + output.len {
+            output_buffer[i] = lut.apply_color(colors[i]);
+        }
+    }
+}
+
+// `f64::powf` isn't available in const contexts or in `core`, so the tables are built with
+// these instead. They only need to be accurate over the 0..=1 range the tables use.
+
+const fn ln(x: f64) -> f64 {
+    // Reduce to m * 2^k with m in [0.5, 1), then ln(m) = 2 * atanh((m - 1) / (m + 1)).
+    let mut m = x;
+    let mut k = 0;
+    while m >= 1.0 {
+        m /= 2.0;
+        k += 1;
+    }
+    while m < 0.5 {
+        m *= 2.0;
+        k -= 1;
+    }
+    let z = (m - 1.0) / (m + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut sum = 0.0;
+    let mut n = 1.0;
+    while n < 40.0 {
+        sum += term / n;
+        term *= z2;
+        n += 2.0;
+    }
+    2.0 * sum + k as f64 * core::f64::consts::LN_2
+}
+
+const fn exp(x: f64) -> f64 {
+    // Reduce to r + n * ln(2) with r in [0, ln(2)), then use the Taylor series for e^r.
+    let mut n = (x / core::f64::consts::LN_2) as i32;
+    if (n as f64) * core::f64::consts::LN_2 > x {
+        n -= 1;
+    }
+    let r = x - n as f64 * core::f64::consts::LN_2;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut i = 1;
+    while i < 20 {
+        term *= r / i as f64;
+        sum += term;
+        i += 1;
+    }
+    while n > 0 {
+        sum *= 2.0;
+        n -= 1;
+    }
+    while n < 0 {
+        sum /= 2.0;
+        n += 1;
+    }
+    sum
+}
+
+const fn powf(base: f64, exponent: f64) -> f64 {
+    if base <= 0.0 {
+        0.0
+    } else {
+        exp(exponent * ln(base))
+    }
+}
@@ -0,0 +1,21 @@
+//! Input events shared between the input tasks and the control task.
+//!
+//! Every input source pushes `InputEvent`s into a single `InputChannel`, so the control task only
+//! ever has one queue to drain no matter how many buttons (or later, network sources) exist.
+
+use crate::button;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, Receiver, Sender};
+
+/// Number of events that can be queued before input tasks have to wait on the control task.
+pub const INPUT_QUEUE_LEN: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    /// An event from one of the physical buttons. `id` matches the GPIO number of the button.
+    Button { id: u8, event: button::Event },
+}
+
+pub type InputChannel = Channel<CriticalSectionRawMutex, InputEvent, INPUT_QUEUE_LEN>;
+pub type InputSender = Sender<'static, CriticalSectionRawMutex, InputEvent, INPUT_QUEUE_LEN>;
+pub type InputReceiver = Receiver<'static, CriticalSectionRawMutex, InputEvent, INPUT_QUEUE_LEN>;
@@ -0,0 +1,174 @@
+//! Declarative description of the walls in a room.
+//!
+//! A `Layout` lists every wall in the order its LEDs appear in the logical buffer, along with its
+//! length, the physical strip it is on, and whether the animation should run against the
+//! strip's direction. Start indices, strip lengths, translation arrays and correction zones are
+//! all derived from it by `const fn`, so a layout is written down once and a mistake in it fails
+//! the build instead of showing up as a misplaced animation.
+
+use crate::correction::{ColorCorrection, CorrectionZone};
+
+/// One wall's run of LEDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wall {
+    /// Index in the `OutputMap` of the strip the wall is on.
+    pub output: usize,
+    /// Number of LEDs on the wall.
+    pub len: usize,
+    /// Set if the wall's animation runs from the far end of its LEDs back towards the start of
+    /// the strip.
+    pub reverse: bool,
+}
+
+/// The walls of a room, spread over `S` physical strips.
+pub struct Layout<const W: usize, const S: usize> {
+    pub walls: [Wall; W],
+}
+
+impl<const W: usize, const S: usize> Layout<W, S> {
+    /// Creates a new layout. Panics, failing the build when the layout is a `const`, if a wall is
+    /// empty or on a strip that doesn't exist, if a strip has no walls, or if a strip's walls
+    /// aren't listed next to each other.
+    pub const fn new(walls: [Wall; W]) -> Self {
+        assert!(W > 0, "Layouts must have at least one wall.");
+        let mut output = 0;
+        let mut i = 0;
+        while i < W {
+            assert!(walls[i].len > 0, "Walls must have at least one LED.");
+            assert!(
+                walls[i].output < S,
+                "Wall is on an output that doesn't exist."
+            );
+            if walls[i].output != output {
+                assert!(
+                    walls[i].output == output + 1,
+                    "Walls must be listed in output order, and every output needs a wall."
+                );
+                output += 1;
+            }
+            i += 1;
+        }
+        assert!(output == S - 1, "Every output needs at least one wall.");
+        Layout { walls }
+    }
+
+    /// Index in the logical buffer of the first LED on `wall`.
+    pub const fn start_index(&self, wall: usize) -> usize {
+        let mut start = 0;
+        let mut i = 0;
+        while i < wall {
+            start += self.walls[i].len;
+            i += 1;
+        }
+        start
+    }
+
+    /// Number of LEDs on `wall`.
+    pub const fn wall_len(&self, wall: usize) -> usize {
+        self.walls[wall].len
+    }
+
+    /// Total number of LEDs in the layout.
+    pub const fn num_leds(&self) -> usize {
+        self.start_index(W)
+    }
+
+    /// The wall LED `index` of the logical buffer is on.
+    pub const fn wall_at(&self, index: usize) -> usize {
+        let mut wall = 0;
+        while wall < W - 1 && index >= self.start_index(wall + 1) {
+            wall += 1;
+        }
+        wall
+    }
+
+    /// Index in the logical buffer of the first LED on physical strip `output`.
+    pub const fn output_start(&self, output: usize) -> usize {
+        let mut i = 0;
+        while self.walls[i].output != output {
+            i += 1;
+        }
+        self.start_index(i)
+    }
+
+    /// Number of LEDs on physical strip `output`.
+    pub const fn output_len(&self, output: usize) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < W {
+            if self.walls[i].output == output {
+                len += self.walls[i].len;
+            }
+            i += 1;
+        }
+        len
+    }
+
+    /// The translation array for `wall`'s animation, mapping each animation pixel to its index in
+    /// the logical buffer. `N` has to match the wall's length.
+    pub const fn translation_array<const N: usize>(&self, wall: usize) -> [usize; N] {
+        assert!(
+            N == self.walls[wall].len,
+            "Translation array length must match the wall."
+        );
+        let mut array = [0; N];
+        let mut i = 0;
+        while i < N {
+            array[i] = self.animation_pixel(wall, i);
+            i += 1;
+        }
+        array
+    }
+
+    /// The translation array for one animation running around the whole room, through every wall
+    /// in the order listed in `order`. Each wall is run in the same direction as its own
+    /// animation. `N` has to match the number of LEDs in the layout.
+    pub const fn loop_translation_array<const N: usize>(&self, order: [usize; W]) -> [usize; N] {
+        assert!(
+            N == self.num_leds(),
+            "Loop translation array length must match the layout."
+        );
+        let mut used = [false; W];
+        let mut array = [0; N];
+        let mut next = 0;
+        let mut i = 0;
+        while i < W {
+            let wall = order[i];
+            assert!(!used[wall], "Every wall must be on the loop exactly once.");
+            used[wall] = true;
+            let mut j = 0;
+            while j < self.walls[wall].len {
+                array[next] = self.animation_pixel(wall, j);
+                next += 1;
+                j += 1;
+            }
+            i += 1;
+        }
+        array
+    }
+
+    /// Index in the logical buffer of pixel `i` of `wall`'s animation.
+    pub const fn animation_pixel(&self, wall: usize, i: usize) -> usize {
+        let start = self.start_index(wall);
+        if self.walls[wall].reverse {
+            start + self.walls[wall].len - 1 - i
+        } else {
+            start + i
+        }
+    }
+
+    /// One correction zone per wall, using the matching entry of `corrections`.
+    pub const fn correction_zones(&self, corrections: [ColorCorrection; W]) -> [CorrectionZone; W] {
+        let mut zones = [CorrectionZone { start: 0, len: 0, correction: corrections[0] }; W];
+        let mut i = 0;
+        while i < W {
+            zones[i] = CorrectionZone {
+                start: self.start_index(i),
+                len: self.walls[i].len,
+                correction: corrections[i],
+            };
+            i += 1;
+        }
+        zones
+    }
+}
@@ -1,4 +1,26 @@
 #![no_std]
+pub mod apa102;
+pub mod button;
+pub mod calibration;
+pub mod combo;
+pub mod control;
+pub mod correction;
+pub mod dither;
+pub mod error;
+pub mod frame_scheduler;
+pub mod gamma;
+pub mod input;
+pub mod layout;
+pub mod linear;
+pub mod output;
+pub mod power;
+pub mod psu;
+pub mod refresh;
+pub mod rgbw;
+pub mod spatial;
+pub mod telemetry;
+pub mod transition;
+
 pub mod default_consts {
 
     use rgb::RGB8;
@@ -134,27 +156,10 @@ pub mod default_consts {
     ];
 }
 
-pub mod office_lights {
-    // index for LED strip in logical array
-    pub const START_CLOSET_INDEX: usize = 0;
-    pub const START_WINDOW_INDEX: usize = NUM_LEDS_CLOSET_WALL;
-    pub const START_DOOR_INDEX: usize = START_WINDOW_INDEX + NUM_LEDS_WINDOW_WALL;
-    pub const START_NORTH_INDEX: usize = START_DOOR_INDEX + NUM_LEDS_DOOR_WALL;
-
-    pub const NUM_LEDS_CLOSET_WALL: usize = 202;
-    pub const NUM_LEDS_WINDOW_WALL: usize = 293;
-    pub const NUM_LEDS_DOOR_WALL: usize = 292;
-    pub const NUM_LEDS_NORTH_WALL: usize = 202;
-}
-
-pub mod test_strip {
-    // index for LED strip in logical array
-    pub const START_CLOSET_INDEX: usize = 0;
-    pub const START_WINDOW_INDEX: usize = NUM_LEDS_CLOSET_WALL;
-    pub const START_DOOR_INDEX: usize = START_WINDOW_INDEX + NUM_LEDS_WINDOW_WALL;
-
-    pub const NUM_LEDS_CLOSET_WALL: usize = 55;
-    pub const NUM_LEDS_WINDOW_WALL: usize = 55;
-    pub const NUM_LEDS_DOOR_WALL: usize = 51;
-    pub const NUM_LEDS_NORTH_WALL: usize = 51;
+// The room the firmware is built for, generated by build.rs from the layout file picked with the
+// `office_lights` or `test_strip` feature or the ROOM_LAYOUT environment variable. The layout files
+// live in layouts/.
+#[cfg(room_layout)]
+pub mod room {
+    include!(concat!(env!("OUT_DIR"), "/room.rs"));
 }
@@ -0,0 +1,117 @@
+//! High bit depth, linear light render pipeline.
+//!
+//! In the default pipeline every stage after gamma works on 8-bit colors, so each dimming or
+//! correction step rounds again and low brightness fades band. A `LinearBuffer` instead holds
+//! the whole frame as 8.8 fixed point LED duty (the same units as `GammaLut::apply_fixed`).
+//! Colors are decoded through the gamma tables once, brightness, correction and crossfades all
+//! happen at full precision, and the frame is quantized to 8 bits exactly once at the end.
+
+use crate::correction::{CorrectionZone, NO_CORRECTION};
+use crate::dither::TemporalDither;
+use crate::gamma::GammaLut;
+use crate::output::OutputMap;
+use rgb::{RGB8, RGB16};
+
+/// Highest value a channel can hold, matching an 8-bit value of 255.
+pub const FULL_SCALE: u16 = 255 << 8;
+
+pub struct LinearBuffer<const N: usize> {
+    pub colors: [RGB16; N],
+}
+
+impl<const N: usize> LinearBuffer<N> {
+    pub const fn new() -> Self {
+        LinearBuffer { colors: [RGB16 { r: 0, g: 0, b: 0 }; N] }
+    }
+
+    /// Fills the buffer from the logical color buffer, using each output's gamma table.
+    pub fn decode<const M: usize>(
+        &mut self,
+        outputs: &OutputMap<M>,
+        luts: &[GammaLut; M],
+        colors: &[RGB8],
+    ) {
+        for (output, lut) in outputs.outputs.iter().zip(luts) {
+            let range = output.start..output.start + output.len;
+            for (linear, color) in self.colors[range.clone()].iter_mut().zip(&colors[range]) {
+                *linear = RGB16 {
+                    r: lut.apply_fixed(color.r),
+                    g: lut.apply_fixed(color.g),
+                    b: lut.apply_fixed(color.b),
+                };
+            }
+        }
+    }
+
+    /// Scales `len` LEDs starting at `start` by `scale / 65535`.
+    pub fn scale(&mut self, start: usize, len: usize, scale: u16) {
+        let scale_channel =
+            |channel: u16| ((channel as u32 * scale as u32 + 32_767) / 65_535) as u16;
+        for color in self.colors[start..start + len].iter_mut() {
+            *color = RGB16 {
+                r: scale_channel(color.r),
+                g: scale_channel(color.g),
+                b: scale_channel(color.b),
+            };
+        }
+    }
+
+    /// Applies every zone's brightness and white balance correction.
+    pub fn apply_corrections(&mut self, zones: &[CorrectionZone]) {
+        for zone in zones.iter().filter(|zone| zone.correction != NO_CORRECTION) {
+            for color in self.colors[zone.start..zone.start + zone.len].iter_mut() {
+                *color = zone.correction.apply_fixed(*color);
+            }
+        }
+    }
+
+    /// Blends this buffer towards `target`. An `amount` of 0 leaves the buffer as it is, 65535
+    /// replaces it with `target`.
+    pub fn crossfade(&mut self, target: &[RGB16], amount: u16) {
+        // A full scale step times a full blend doesn't fit in an i32, and the rounding has to
+        // floor so that fading down rounds the same way as fading up.
+        let blend = |from: u16, to: u16| {
+            let step = (to as i64 - from as i64) * amount as i64;
+            (from as i64 + (step + 32_767).div_euclid(65_535)) as u16
+        };
+        for (color, target) in self.colors.iter_mut().zip(target) {
+            *color = RGB16 {
+                r: blend(color.r, target.r),
+                g: blend(color.g, target.g),
+                b: blend(color.b, target.b),
+            };
+        }
+    }
+
+    /// Quantizes the frame to 8 bits into `output_buffer`. Outputs with `dither` set go through
+    /// `dither`, the rest are rounded.
+    pub fn quantize<const M: usize>(
+        &self,
+        outputs: &OutputMap<M>,
+        output_buffer: &mut [RGB8],
+        dither: &mut TemporalDither<N>,
+    ) {
+        let round = |channel: u16| ((channel as u32 + 128) >> 8).min(255) as u8;
+        for output in outputs.outputs.iter() {
+            let leds = output_buffer
+                .iter_mut()
+                .enumerate()
+                .skip(output.start)
+                .take(output.len);
+            for (i, out) in leds {
+                let color = self.colors[i];
+                *out = if output.dither {
+                    dither.quantize(i, color)
+                } else {
+                    RGB8 { r: round(color.r), g: round(color.g), b: round(color.b) }
+                };
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for LinearBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
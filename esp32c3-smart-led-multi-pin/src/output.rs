@@ -0,0 +1,275 @@
+//! Mapping from the logical color buffer to the physical LED strips.
+//!
+//! An `OutputMap` lists every physical strip along with the slice of the logical buffer it shows.
+//! The render loop sends each strip's adapter the colors from `Output::colors`, which take care of
+//! slicing, direction and channel order, so adding a strip is a matter of adding an `[[output]]`
+//! entry to the layout file.
+//!
+//! Strips with LEDs cut out or bypassed after a failure have fewer LEDs than their slice of the
+//! logical buffer. Those list what each of their LEDs shows in `leds`, so the rest of the strip
+//! still lines up with the walls and the animations don't need to know.
+
+use crate::rgbw::RgbwConfig;
+use rgb::RGB8;
+
+/// The kind of pixels on a strip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Three channel strips such as the WS2812.
+    Rgb,
+    /// Four channel strips such as the SK6812 RGBW, converted with the config returned by
+    /// `Output::rgbw_config`.
+    Rgbw(RgbwConfig),
+}
+
+/// The order a strip expects the color channels in on the wire.
+///
+/// `esp_hal_smartled` always sends channels in WS2812 order (green, red, blue), so strips wired
+/// differently get their channels shuffled before they are handed to the adapter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl ColorOrder {
+    /// Rearranges `color` so that an adapter sending green, red, blue puts the channels on the
+    /// wire in this order.
+    pub const fn reorder(&self, color: RGB8) -> RGB8 {
+        let RGB8 { r, g, b } = color;
+        // The adapter sends `.g` first, then `.r`, then `.b`.
+        let (first, second, third) = match self {
+            ColorOrder::Rgb => (r, g, b),
+            ColorOrder::Rbg => (r, b, g),
+            ColorOrder::Grb => (g, r, b),
+            ColorOrder::Gbr => (g, b, r),
+            ColorOrder::Brg => (b, r, g),
+            ColorOrder::Bgr => (b, g, r),
+        };
+        RGB8 { r: second, g: first, b: third }
+    }
+}
+
+/// How a strip is connected to the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Driver {
+    /// One-wire strips such as the WS2812, sent from an RMT channel with data on `pin`.
+    Rmt { channel: u8, pin: u8 },
+    /// Clocked APA102 / SK9822 strips, sent from the SPI peripheral with data on `data_pin` and
+    /// the clock on `clock_pin`. They always take blue, green, red, so their `order` is `Grb`,
+    /// which leaves colors as they are.
+    Spi { data_pin: u8, clock_pin: u8 },
+}
+
+/// What one LED on a strip with an explicit `leds` list shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicalLed {
+    /// The color at this index of the logical buffer.
+    Logical(usize),
+    /// Always black, for dead or flickering LEDs still in the chain.
+    Black,
+}
+
+/// One physical strip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub driver: Driver,
+    /// Index in the logical color buffer of the first LED on this strip.
+    pub start: usize,
+    /// Number of LEDs in the strip's slice of the logical buffer. This is the number of LEDs on
+    /// the strip unless `leds` says otherwise.
+    pub len: usize,
+    /// Set if the strip's first LED shows the last color of its slice of the logical buffer.
+    pub reverse: bool,
+    /// Set to use temporal dithering on this strip for smoother low brightness fades.
+    pub dither: bool,
+    pub format: PixelFormat,
+    /// Channel order of the strip, WS2812 strips are usually `Grb`.
+    pub order: ColorOrder,
+    /// What each LED on the strip shows, in wire order, for strips with missing or masked LEDs.
+    /// `None` when every LED shows its own position in the strip's slice.
+    pub leds: Option<&'static [PhysicalLed]>,
+}
+
+impl Output {
+    /// An RGB strip on RMT channel 0 and GPIO 6 showing the `len` colors of the logical buffer
+    /// from `start`, in WS2812 channel order and with every other setting off. Other strips can
+    /// be built from it with struct update syntax.
+    pub const fn rgb(start: usize, len: usize) -> Self {
+        Output {
+            driver: Driver::Rmt { channel: 0, pin: 6 },
+            start,
+            len,
+            reverse: false,
+            dither: false,
+            format: PixelFormat::Rgb,
+            order: ColorOrder::Grb,
+            leds: None,
+        }
+    }
+
+    /// Number of LEDs actually on the strip.
+    pub const fn physical_len(&self) -> usize {
+        match self.leds {
+            Some(leds) => leds.len(),
+            None => self.len,
+        }
+    }
+
+    /// Number of colors sent to the strip's LED adapter each frame, which is the length of its
+    /// buffer. RGBW LEDs take four bytes each, so their channels are packed three to a color.
+    pub const fn adapter_len(&self) -> usize {
+        match self.format {
+            PixelFormat::Rgb => self.physical_len(),
+            PixelFormat::Rgbw(_) => (self.physical_len() * 4).div_ceil(3),
+        }
+    }
+
+    /// Returns the index into the logical color buffer shown by the LED at `physical_index` on
+    /// this strip, or `None` if that LED is always black.
+    pub const fn logical_index(&self, physical_index: usize) -> Option<usize> {
+        match self.leds {
+            Some(leds) => match leds[physical_index] {
+                PhysicalLed::Logical(index) => Some(index),
+                PhysicalLed::Black => None,
+            },
+            None if self.reverse => Some(self.start + self.len - 1 - physical_index),
+            None => Some(self.start + physical_index),
+        }
+    }
+
+    /// Returns the colors to hand this strip's LED adapter, in the order they need to be sent
+    /// down the wire.
+    ///
+    /// The adapter sends three bytes per color, so the four bytes of each LED on an RGBW strip
+    /// are packed into the colors back to back, and the bytes left over in the last color are
+    /// clocked out past the end of the strip as zeros.
+    pub fn colors<'a>(&self, buffer: &'a [RGB8]) -> impl Iterator<Item = RGB8> + 'a {
+        let output = *self;
+        let rgbw = self.rgbw_config();
+        (0..self.adapter_len()).map(move |i| match rgbw {
+            None => output.color(buffer, i),
+            Some(config) => {
+                // Color `i` carries bytes `3 * i` to `3 * i + 2` of the strip, which come from at
+                // most two LEDs.
+                let led = 3 * i / 4;
+                let [a, b] = [led, led + 1].map(|led| output.rgbw_bytes(config, buffer, led));
+                let bytes = [a[0], a[1], a[2], a[3], b[0], b[1], b[2], b[3]];
+                let offset = 3 * i % 4;
+                // The adapter sends `.g` first, then `.r`, then `.b`.
+                RGB8 { r: bytes[offset + 1], g: bytes[offset], b: bytes[offset + 2] }
+            }
+        })
+    }
+
+    /// The color of LED `physical_index`, with its channels in the order the adapter needs.
+    fn color(&self, buffer: &[RGB8], physical_index: usize) -> RGB8 {
+        match self.logical_index(physical_index) {
+            Some(index) => self.order.reorder(buffer[index]),
+            None => RGB8::default(),
+        }
+    }
+
+    /// The four bytes LED `physical_index` of an RGBW strip is sent, or zeros past the end of the
+    /// strip.
+    fn rgbw_bytes(&self, config: RgbwConfig, buffer: &[RGB8], physical_index: usize) -> [u8; 4] {
+        if physical_index >= self.physical_len() {
+            return [0; 4];
+        }
+        let color = config.convert(self.color(buffer, physical_index));
+        // Reordered like an RGB color, so the first channel on the wire is in `.g`.
+        [color.g, color.r, color.b, color.a.0]
+    }
+
+    /// The config to convert this output's colors to RGBW with, or `None` for RGB strips.
+    /// The colors handed to the adapter are already in wire order, so the white point is
+    /// reordered to match.
+    pub const fn rgbw_config(&self) -> Option<RgbwConfig> {
+        match self.format {
+            PixelFormat::Rgb => None,
+            PixelFormat::Rgbw(config) => Some(RgbwConfig {
+                white_point: self.order.reorder(config.white_point),
+                ..config
+            }),
+        }
+    }
+}
+
+pub struct OutputMap<const N: usize> {
+    pub outputs: [Output; N],
+}
+
+impl<const N: usize> OutputMap<N> {
+    /// Creates a new output map. Panics if any output is empty, two outputs share LEDs, the
+    /// outputs leave gaps in the logical buffer, or an output's `leds` show colors from outside
+    /// its slice, which fails the build when the map is a `const`.
+    pub const fn new(outputs: [Output; N]) -> Self {
+        let mut covered = 0;
+        let mut i = 0;
+        while i < N {
+            covered += outputs[i].len;
+            assert!(outputs[i].len > 0, "Outputs must have at least one LED.");
+            assert!(
+                outputs[i].physical_len() > 0,
+                "Outputs must have at least one LED."
+            );
+            if let Some(leds) = outputs[i].leds {
+                let mut led = 0;
+                while led < leds.len() {
+                    if let PhysicalLed::Logical(index) = leds[led] {
+                        assert!(
+                            index >= outputs[i].start && index < outputs[i].start + outputs[i].len,
+                            "Output LEDs must show colors from the output's own slice."
+                        );
+                    }
+                    led += 1;
+                }
+            }
+            let mut j = i + 1;
+            while j < N {
+                let a = &outputs[i];
+                let b = &outputs[j];
+                assert!(
+                    a.start + a.len <= b.start || b.start + b.len <= a.start,
+                    "Outputs must not overlap in the logical buffer."
+                );
+                j += 1;
+            }
+            i += 1;
+        }
+        let map = OutputMap { outputs };
+        assert!(
+            covered == map.total_len(),
+            "Outputs must cover the logical buffer without gaps."
+        );
+        map
+    }
+
+    /// The size of the logical buffer needed to cover every output.
+    pub const fn total_len(&self) -> usize {
+        let mut end = 0;
+        let mut i = 0;
+        while i < N {
+            let output_end = self.outputs[i].start + self.outputs[i].len;
+            if output_end > end {
+                end = output_end;
+            }
+            i += 1;
+        }
+        end
+    }
+
+    /// The output showing LED `logical_index` of the logical buffer and the LED's position on
+    /// that strip, or `None` if it isn't shown because its LED is missing.
+    pub fn physical_index(&self, logical_index: usize) -> Option<(usize, usize)> {
+        self.outputs.iter().enumerate().find_map(|(i, output)| {
+            (0..output.physical_len())
+                .find(|&led| output.logical_index(led) == Some(logical_index))
+                .map(|led| (i, led))
+        })
+    }
+}
@@ -0,0 +1,178 @@
+//! Power budget estimation and current limiting.
+//!
+//! WS2812 current draw is close to linear in the PWM duty of each channel, so the current a frame
+//! will pull can be estimated from the final, post-gamma colors. The `PowerLimiter` does that for
+//! each supply, and if a supply would be over budget it scales down the colors on the LEDs it
+//! feeds until the estimate fits. The figures for the room's LEDs come from the `[power]` table
+//! of its layout file. With the linear pipeline the limit is applied to the `LinearBuffer`
+//! instead, so dimming an over budget supply doesn't round the colors an extra time.
+
+use crate::linear::LinearBuffer;
+use crate::output::{Output, OutputMap};
+use rgb::{RGB8, RGB16};
+
+/// Current draw of a single LED package. All values are in microamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowerModel {
+    /// Current through one color channel at full duty (255).
+    pub channel_full_ua: u32,
+    /// Current drawn by the LED's controller while showing black.
+    pub idle_ua: u32,
+}
+
+impl PowerModel {
+    /// Estimated current in microamps for showing `colors`.
+    pub fn estimate_ua(&self, colors: impl Iterator<Item = RGB8>) -> u64 {
+        let (led_count, channel_sum) = colors.fold((0_u64, 0_u64), |(count, sum), c| {
+            (count + 1, sum + c.r as u64 + c.g as u64 + c.b as u64)
+        });
+        led_count * self.idle_ua as u64 + channel_sum * self.channel_full_ua as u64 / 255
+    }
+
+    /// Like `estimate_ua`, for the 8.8 fixed point colors of a `LinearBuffer`.
+    pub fn estimate_fixed_ua(&self, colors: impl Iterator<Item = RGB16>) -> u64 {
+        let (led_count, channel_sum) = colors.fold((0_u64, 0_u64), |(count, sum), c| {
+            (count + 1, sum + c.r as u64 + c.g as u64 + c.b as u64)
+        });
+        led_count * self.idle_ua as u64 + channel_sum * self.channel_full_ua as u64 / (255 << 8)
+    }
+}
+
+/// A power supply and the outputs it feeds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Supply {
+    /// Indices into the `OutputMap` of the strips powered by this supply.
+    pub outputs: &'static [usize],
+    /// Current the LEDs on this supply are allowed to draw in total.
+    pub budget_ma: u32,
+}
+
+/// What one supply was estimated to draw for the last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SupplyEstimate {
+    /// Estimated draw of the frame as rendered.
+    pub requested_ma: u32,
+    /// Estimated draw after limiting.
+    pub delivered_ma: u32,
+    /// Scale applied to the colors on this supply, out of 256. 256 means no limiting was needed.
+    pub scale: u16,
+}
+
+pub struct PowerLimiter<const S: usize> {
+    pub model: PowerModel,
+    pub supplies: [Supply; S],
+}
+
+impl<const S: usize> PowerLimiter<S> {
+    pub const fn new(model: PowerModel, supplies: [Supply; S]) -> Self {
+        PowerLimiter { model, supplies }
+    }
+
+    /// Scales the colors in `buffer` so that every supply stays within its budget, and returns
+    /// the current estimates for each supply. The estimates are for the LEDs actually on each
+    /// strip, so LEDs cut out of a strip draw nothing and masked ones only their idle current.
+    pub fn limit<const N: usize>(
+        &self,
+        outputs: &OutputMap<N>,
+        buffer: &mut [RGB8],
+    ) -> [SupplyEstimate; S] {
+        let mut estimates = [SupplyEstimate::default(); S];
+
+        for (supply, estimate) in self.supplies.iter().zip(estimates.iter_mut()) {
+            let supply_outputs = || supply.outputs.iter().map(|&i| &outputs.outputs[i]);
+            let frame: &[RGB8] = buffer;
+            let requested_ua = self
+                .model
+                .estimate_ua(supply_outputs().flat_map(|o| o.colors(frame)));
+            *estimate = self.fit_to_budget(supply, supply_outputs(), requested_ua);
+
+            let scale = estimate.scale;
+            if scale < 256 {
+                for output in supply_outputs() {
+                    for c in &mut buffer[output.start..output.start + output.len] {
+                        *c = RGB8 {
+                            r: (c.r as u16 * scale / 256) as u8,
+                            g: (c.g as u16 * scale / 256) as u8,
+                            b: (c.b as u16 * scale / 256) as u8,
+                        };
+                    }
+                }
+            }
+        }
+
+        estimates
+    }
+
+    /// Like `limit`, for a frame in a `LinearBuffer`. The colors are scaled at full precision and
+    /// only rounded when the buffer is quantized.
+    pub fn limit_linear<const N: usize, const M: usize>(
+        &self,
+        outputs: &OutputMap<N>,
+        buffer: &mut LinearBuffer<M>,
+    ) -> [SupplyEstimate; S] {
+        let mut estimates = [SupplyEstimate::default(); S];
+
+        for (supply, estimate) in self.supplies.iter().zip(estimates.iter_mut()) {
+            let supply_outputs = || supply.outputs.iter().map(|&i| &outputs.outputs[i]);
+            let frame = &buffer.colors;
+            let physical_colors = |output: &Output| {
+                let output = *output;
+                (0..output.physical_len()).map(move |led| match output.logical_index(led) {
+                    Some(index) => frame[index],
+                    None => RGB16::default(),
+                })
+            };
+            let requested_ua = self
+                .model
+                .estimate_fixed_ua(supply_outputs().flat_map(physical_colors));
+            *estimate = self.fit_to_budget(supply, supply_outputs(), requested_ua);
+
+            if estimate.scale < 256 {
+                let scale = (estimate.scale as u32 * 65_535 / 256) as u16;
+                for output in supply_outputs() {
+                    buffer.scale(output.start, output.len, scale);
+                }
+            }
+        }
+
+        estimates
+    }
+
+    // Works out how far the colors on `supply` have to be scaled for `requested_ua` to fit its
+    // budget. Only the color current can be scaled, the idle current is always there.
+    fn fit_to_budget<'a>(
+        &self,
+        supply: &Supply,
+        outputs: impl Iterator<Item = &'a Output>,
+        requested_ua: u64,
+    ) -> SupplyEstimate {
+        let led_count: u64 = outputs.map(|o| o.physical_len() as u64).sum();
+        let idle_ua = led_count * self.model.idle_ua as u64;
+        let budget_ua = supply.budget_ma as u64 * 1000;
+
+        let scale = if requested_ua <= budget_ua {
+            256
+        } else {
+            let color_ua = requested_ua - idle_ua;
+            let allowed_color_ua = budget_ua.saturating_sub(idle_ua);
+            (allowed_color_ua * 256 / color_ua.max(1)) as u16
+        };
+
+        let delivered_ua = idle_ua + (requested_ua - idle_ua) * scale as u64 / 256;
+        SupplyEstimate {
+            requested_ma: (requested_ua / 1000) as u32,
+            delivered_ma: (delivered_ua / 1000) as u32,
+            scale,
+        }
+    }
+
+    /// The scale out of 256 that `limit` or `limit_linear` applied to output `output`, given the
+    /// `estimates` it returned.
+    pub fn output_scale(&self, estimates: &[SupplyEstimate; S], output: usize) -> u16 {
+        self.supplies
+            .iter()
+            .zip(estimates)
+            .find(|(supply, _)| supply.outputs.contains(&output))
+            .map_or(256, |(_, estimate)| estimate.scale)
+    }
+}
@@ -0,0 +1,78 @@
+//! LED power supply switching.
+//!
+//! Even showing black, every LED draws its idle current, which adds up over a night with the
+//! `BLACK_RAINBOW` palette. `PsuControl` switches the LED supplies off through a relay or MOSFET
+//! once the output has been black for a while, and back on as soon as there is something to
+//! show. After switching on it holds frames back until the supply has settled, since LEDs that
+//! are still powering up can latch garbage.
+
+/// Timing and wiring of the supply switch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PsuConfig {
+    /// How long the output has to be black before the supply is switched off.
+    pub off_after_black_us: u64,
+    /// How long to wait after switching the supply on before sending the first frame.
+    pub settle_us: u64,
+    /// Set if driving the control pin high switches the supply on.
+    pub active_high: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PsuState {
+    /// Supply on and frames being shown. Holds the time the output went black, if it is black.
+    On { black_since_us: Option<u64> },
+    /// Supply on, waiting until `until_us` for it to settle.
+    Settling { until_us: u64 },
+    /// Supply off.
+    Off,
+}
+
+pub struct PsuControl {
+    config: PsuConfig,
+    state: PsuState,
+}
+
+impl PsuControl {
+    /// Creates a controller with the supply off. The first non-black frame switches it on.
+    pub const fn new(config: PsuConfig) -> Self {
+        PsuControl { config, state: PsuState::Off }
+    }
+
+    /// Advances the state machine for the frame about to be shown. Returns `true` if the frame
+    /// should be sent to the strips, `false` if the supply is off or still settling.
+    pub fn update(&mut self, now_us: u64, frame_is_black: bool) -> bool {
+        self.state = match self.state {
+            PsuState::On { black_since_us } if frame_is_black => {
+                let black_since_us = black_since_us.unwrap_or(now_us);
+                if now_us - black_since_us >= self.config.off_after_black_us {
+                    PsuState::Off
+                } else {
+                    PsuState::On { black_since_us: Some(black_since_us) }
+                }
+            }
+            PsuState::On { .. } => PsuState::On { black_since_us: None },
+            PsuState::Off if !frame_is_black => {
+                PsuState::Settling { until_us: now_us + self.config.settle_us }
+            }
+            PsuState::Settling { until_us } if now_us >= until_us => {
+                PsuState::On { black_since_us: None }
+            }
+            state => state,
+        };
+        matches!(self.state, PsuState::On { .. })
+    }
+
+    pub fn state(&self) -> PsuState {
+        self.state
+    }
+
+    /// Returns `true` if the supply should currently be switched on.
+    pub fn supply_on(&self) -> bool {
+        self.state != PsuState::Off
+    }
+
+    /// The level to drive the control pin to, `true` for high.
+    pub fn pin_level(&self) -> bool {
+        self.supply_on() == self.config.active_high
+    }
+}
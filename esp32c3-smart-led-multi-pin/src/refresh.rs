@@ -0,0 +1,60 @@
+//! Skipping retransmission of unchanged strips.
+//!
+//! Static palettes produce the same output frame after frame. Each strip keeps a hash of the
+//! colors it was last sent, and a frame that hashes the same is not sent again. Line noise can
+//! still glitch an LED that isn't being refreshed, so every strip is resent after a fixed number
+//! of skipped frames regardless.
+//!
+//! Strips with dithering enabled will rarely be skipped while showing in-between levels, since the
+//! dithered output changes from frame to frame even when the animation doesn't.
+
+use rgb::RGB8;
+
+/// FNV-1a hash of a strip's colors.
+pub fn hash_colors(colors: impl Iterator<Item = RGB8>) -> u32 {
+    const OFFSET_BASIS: u32 = 0x811c_9dc5;
+    const PRIME: u32 = 0x0100_0193;
+    colors
+        .flat_map(|c| [c.r, c.g, c.b])
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(PRIME)
+        })
+}
+
+/// Decides whether one strip needs to be sent each frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StripRefresh {
+    last_sent_hash: Option<u32>,
+    frames_since_sent: u32,
+    refresh_interval_frames: u32,
+}
+
+impl StripRefresh {
+    /// Creates a policy that resends an unchanged strip at least every `refresh_interval_frames`
+    /// frames.
+    pub const fn new(refresh_interval_frames: u32) -> Self {
+        StripRefresh {
+            last_sent_hash: None,
+            frames_since_sent: 0,
+            refresh_interval_frames,
+        }
+    }
+
+    /// Returns `true` if the frame with the given hash should be sent, and assumes it will be.
+    pub fn should_send(&mut self, hash: u32) -> bool {
+        self.frames_since_sent += 1;
+        let send = self.last_sent_hash != Some(hash)
+            || self.frames_since_sent >= self.refresh_interval_frames;
+        if send {
+            self.last_sent_hash = Some(hash);
+            self.frames_since_sent = 0;
+        }
+        send
+    }
+
+    /// Forgets what the strip was last sent, so the next frame goes out whatever it contains. Use
+    /// this after a failed write.
+    pub fn invalidate(&mut self) {
+        self.last_sent_hash = None;
+    }
+}
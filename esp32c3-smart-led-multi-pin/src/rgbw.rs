@@ -0,0 +1,102 @@
+//! RGBW (SK6812) output support.
+//!
+//! The render loop works in RGB the whole way through. Strips with a fourth, white LED need their
+//! colors converted right before transmission with `RgbwConfig::convert`, which takes the part of
+//! each color the white LED can show and moves it onto the white channel. The RMT LED adapter only
+//! sends three channels per color, so `Output::colors` packs the four channels of each LED across
+//! the adapter's colors.
+//!
+//! White LEDs aren't pure white, so the extraction is done against the white LED's own color.
+//! Pulling the full `min(r, g, b)` out of a color only gives the right result when the white LED
+//! is exactly as cold as the RGB white; for warm white parts it would turn pale colors orange.
+
+use rgb::RGB8;
+use smart_leds::{RGBW, White};
+
+/// How much of each color gets moved onto the white channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhiteStrategy {
+    /// Leave the white channel off and send the RGB part unchanged.
+    None,
+    /// Move as much of the color onto the white channel as the white LED can reproduce exactly.
+    /// Gives the best efficiency and the cleanest whites.
+    Max,
+    /// Only move the white component of pale colors onto the white channel. Saturated colors are
+    /// left to the RGB LEDs, which keeps them from looking washed out next to pure RGB strips.
+    Saturation,
+}
+
+/// The color of the white LED, as the RGB levels that match it at full brightness.
+pub type WhitePoint = RGB8;
+
+/// Warm white SK6812 parts, around 3000K.
+pub const WARM_WHITE: WhitePoint = RGB8 { r: 255, g: 180, b: 107 };
+/// Neutral white SK6812 parts, around 4500K.
+pub const NEUTRAL_WHITE: WhitePoint = RGB8 { r: 255, g: 219, b: 186 };
+/// Cool white SK6812 parts, around 6500K.
+pub const COOL_WHITE: WhitePoint = RGB8 { r: 255, g: 254, b: 250 };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RgbwConfig {
+    pub strategy: WhiteStrategy,
+    pub white_point: WhitePoint,
+}
+
+impl RgbwConfig {
+    /// Converts one rendered color into the RGBW levels to send to the strip.
+    pub const fn convert(&self, color: RGB8) -> RGBW<u8> {
+        let max_white = match self.strategy {
+            WhiteStrategy::None => 0,
+            WhiteStrategy::Max | WhiteStrategy::Saturation => {
+                // The white LED at level `w` looks like `w * white_point / 255`, so the most white
+                // that fits is the smallest `channel * 255 / white_point` over the three channels.
+                let r = white_limit(color.r, self.white_point.r);
+                let g = white_limit(color.g, self.white_point.g);
+                let b = white_limit(color.b, self.white_point.b);
+                min(r, min(g, b))
+            }
+        };
+        let white = match self.strategy {
+            WhiteStrategy::Saturation => {
+                // Scale by how unsaturated the color is: all of it for greys, none for pure hues.
+                let (r, g, b) = (color.r as u32, color.g as u32, color.b as u32);
+                let high = max(r, max(g, b));
+                let low = min(r, min(g, b));
+                match (max_white * low).checked_div(high) {
+                    Some(white) => white,
+                    None => 0,
+                }
+            }
+            _ => max_white,
+        };
+        RGBW {
+            r: color.r - white_part(white, self.white_point.r),
+            g: color.g - white_part(white, self.white_point.g),
+            b: color.b - white_part(white, self.white_point.b),
+            a: White(white as u8),
+        }
+    }
+}
+
+/// The highest white level whose contribution to one channel stays within `channel`.
+const fn white_limit(channel: u8, white_point: u8) -> u32 {
+    if white_point == 0 {
+        255
+    } else {
+        min(channel as u32 * 255 / white_point as u32, 255)
+    }
+}
+
+/// How much one channel is lit by the white LED at level `white`. Rounds down, so it never
+/// exceeds what `white_limit` allowed for.
+const fn white_part(white: u32, white_point: u8) -> u8 {
+    (white * white_point as u32 / 255) as u8
+}
+
+const fn min(a: u32, b: u32) -> u32 {
+    if a < b { a } else { b }
+}
+
+const fn max(a: u32, b: u32) -> u32 {
+    if a > b { a } else { b }
+}
@@ -0,0 +1,175 @@
+//! Positions of the LEDs in the room.
+//!
+//! Translation arrays only place an LED along its own wall. A `SpatialMap` gives every LED in the
+//! logical buffer a position in the room, so effects can start from a point (ripples out from the
+//! door) or sweep around it. Positions are worked out from where each wall's first and last LED
+//! are, with the LEDs in between spaced evenly.
+//!
+//! Positions are in millimetres. Angles are binary angles: a full turn is 65536, so they wrap
+//! around naturally in a `u16`. 0 points along +x and `QUARTER_TURN` along +y.
+
+use crate::layout::Layout;
+
+/// A quarter turn, in the binary angle units used by this module.
+pub const QUARTER_TURN: u16 = 0x4000;
+
+/// A point in the room, in millimetres. `z` is height, and is ignored by angles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Point {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Point { x, y, z }
+    }
+
+    /// Straight line distance to `other`.
+    pub const fn distance_mm(&self, other: Point) -> u32 {
+        let dx = (other.x as i64 - self.x as i64).unsigned_abs();
+        let dy = (other.y as i64 - self.y as i64).unsigned_abs();
+        let dz = (other.z as i64 - self.z as i64).unsigned_abs();
+        (dx * dx + dy * dy + dz * dz).isqrt() as u32
+    }
+
+    /// Direction of `other` as seen from here, looking down on the room.
+    pub const fn angle_to(&self, other: Point) -> u16 {
+        atan2(
+            other.y as i64 - self.y as i64,
+            other.x as i64 - self.x as i64,
+        )
+    }
+}
+
+/// Where a wall's LEDs are. `start` is the wall's first LED in the logical buffer and `end` its
+/// last, whichever way the wall's animation runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WallGeometry {
+    pub start: Point,
+    pub end: Point,
+}
+
+/// The position of every LED in an `N` LED logical buffer.
+pub struct SpatialMap<const N: usize> {
+    pub positions: [Point; N],
+}
+
+impl<const N: usize> SpatialMap<N> {
+    /// Places the LEDs of every wall in `layout` evenly between the wall's `start` and `end`.
+    pub const fn new<const W: usize, const S: usize>(
+        layout: &Layout<W, S>,
+        walls: [WallGeometry; W],
+    ) -> Self {
+        assert!(
+            N == layout.num_leds(),
+            "Spatial map length must match the layout."
+        );
+        let mut positions = [Point::new(0, 0, 0); N];
+        let mut wall = 0;
+        while wall < W {
+            let WallGeometry { start, end } = walls[wall];
+            let first = layout.start_index(wall);
+            let len = layout.wall_len(wall);
+            let mut i = 0;
+            while i < len {
+                positions[first + i] = if len == 1 {
+                    start
+                } else {
+                    Point::new(
+                        lerp(start.x, end.x, i, len - 1),
+                        lerp(start.y, end.y, i, len - 1),
+                        lerp(start.z, end.z, i, len - 1),
+                    )
+                };
+                i += 1;
+            }
+            wall += 1;
+        }
+        SpatialMap { positions }
+    }
+
+    pub fn position(&self, led: usize) -> Point {
+        self.positions[led]
+    }
+
+    /// Distance from `from` to LED `led`.
+    pub fn distance_mm(&self, led: usize, from: Point) -> u32 {
+        from.distance_mm(self.positions[led])
+    }
+
+    /// Direction of LED `led` as seen from `from`.
+    pub fn angle(&self, led: usize, from: Point) -> u16 {
+        from.angle_to(self.positions[led])
+    }
+
+    /// Distance from `from` to every LED, in logical buffer order.
+    pub fn distances(&self, from: Point) -> impl Iterator<Item = u32> + '_ {
+        self.positions.iter().map(move |&p| from.distance_mm(p))
+    }
+
+    /// Direction of every LED as seen from `from`, in logical buffer order.
+    pub fn angles(&self, from: Point) -> impl Iterator<Item = u16> + '_ {
+        self.positions.iter().map(move |&p| from.angle_to(p))
+    }
+
+    /// Index into `leds` of the LED nearest to `point`. `leds` is a list of logical buffer
+    /// indices, usually a wall's translation array.
+    pub fn nearest(&self, point: Point, leds: &[usize]) -> Option<usize> {
+        (0..leds.len()).min_by_key(|&i| self.distance_mm(leds[i], point))
+    }
+
+    /// How far along an animation with translation array `translation` its LED nearest to `point`
+    /// is, out of `u16::MAX`. This is the scale `lighting_controller` uses for trigger offsets,
+    /// so a trigger started there begins at the point.
+    pub fn nearest_offset(&self, point: Point, translation: &[usize]) -> u16 {
+        match self.nearest(point, translation) {
+            Some(i) => (i * u16::MAX as usize / translation.len()) as u16,
+            None => 0,
+        }
+    }
+}
+
+/// `i / steps` of the way from `a` to `b`, rounded to the nearest millimetre.
+const fn lerp(a: i32, b: i32, i: usize, steps: usize) -> i32 {
+    let span = (b as i64 - a as i64) * i as i64;
+    let half = steps as i64 / 2;
+    let rounded = if span < 0 { span - half } else { span + half };
+    (a as i64 + rounded / steps as i64) as i32
+}
+
+/// Integer `atan2(y, x)` in binary angle units, accurate to about 0.25 degrees.
+const fn atan2(y: i64, x: i64) -> u16 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let (ax, ay) = (x.unsigned_abs(), y.unsigned_abs());
+    // Angle from the x axis within the first quadrant, built from the first octant.
+    let angle = if ax >= ay {
+        atan_octant(ay, ax)
+    } else {
+        QUARTER_TURN as u32 - atan_octant(ax, ay)
+    };
+    let angle = if x < 0 {
+        2 * QUARTER_TURN as u32 - angle
+    } else {
+        angle
+    };
+    let angle = if y < 0 {
+        4 * QUARTER_TURN as u32 - angle
+    } else {
+        angle
+    };
+    angle as u16
+}
+
+/// `atan(n / d)` for `n <= d`, using `atan(t) ~= t * pi/4 + 0.273 * t * (1 - t)` radians.
+const fn atan_octant(n: u64, d: u64) -> u32 {
+    const ONE: u64 = 1 << 16;
+    let t = n * ONE / d;
+    // pi/4 is an eighth of a turn, and 0.273 radians is 2847.5 binary angle units.
+    let linear = t * (QUARTER_TURN as u64 / 2) / ONE;
+    let correction = 2848 * t * (ONE - t) / (ONE * ONE);
+    (linear + correction) as u32
+}
@@ -0,0 +1,117 @@
+//! Per-frame performance telemetry.
+//!
+//! The render loop times each stage of a frame and records it here. Every so often the collected
+//! min/avg/max values get printed over serial and the window starts over, which is enough to see
+//! whether a layout and frame rate actually fit in the frame budget.
+
+use core::fmt;
+
+/// Rolling min/avg/max for one stage of the frame, in microseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StageStats {
+    pub min_us: u64,
+    pub max_us: u64,
+    pub total_us: u64,
+    pub samples: u32,
+}
+
+impl StageStats {
+    pub const fn new() -> Self {
+        StageStats { min_us: u64::MAX, max_us: 0, total_us: 0, samples: 0 }
+    }
+
+    pub fn record(&mut self, elapsed_us: u64) {
+        self.min_us = self.min_us.min(elapsed_us);
+        self.max_us = self.max_us.max(elapsed_us);
+        self.total_us += elapsed_us;
+        self.samples += 1;
+    }
+
+    pub fn avg_us(&self) -> u64 {
+        if self.samples == 0 {
+            0
+        } else {
+            self.total_us / self.samples as u64
+        }
+    }
+}
+
+impl Default for StageStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for StageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.samples == 0 {
+            return write!(f, "-/-/-");
+        }
+        write!(f, "{}/{}/{}us", self.min_us, self.avg_us(), self.max_us)
+    }
+}
+
+pub struct Telemetry {
+    /// Time spent in `LightingController::update`.
+    pub update: StageStats,
+    /// Time spent turning the logical color buffer into output colors (gamma, brightness, ...).
+    pub color: StageStats,
+    /// Time from the start of the frame until every strip finished transmitting. Transmission
+    /// runs alongside the update and color stages, so this is the wire time of the longest strip
+    /// unless rendering took longer.
+    pub transmit: StageStats,
+    /// Frames that finished after the next frame was due. Filled in from the `FrameScheduler`.
+    pub dropped_frames: u32,
+    /// Strip writes skipped because the strip's colors hadn't changed, across all strips.
+    pub skipped_writes: u32,
+    /// Strip writes that failed, across all strips.
+    pub write_failures: u32,
+    /// Strip adapters set up again after repeated failures.
+    pub reinits: u32,
+    report_interval_frames: u32,
+}
+
+impl Telemetry {
+    /// Creates a new telemetry window that reports every `report_interval_frames` frames.
+    pub const fn new(report_interval_frames: u32) -> Self {
+        Telemetry {
+            update: StageStats::new(),
+            color: StageStats::new(),
+            transmit: StageStats::new(),
+            dropped_frames: 0,
+            skipped_writes: 0,
+            write_failures: 0,
+            reinits: 0,
+            report_interval_frames,
+        }
+    }
+
+    /// Records one frame's stage timings. Returns `true` once enough frames have been collected
+    /// for a report.
+    pub fn record_frame(&mut self, update_us: u64, color_us: u64, transmit_us: u64) -> bool {
+        self.update.record(update_us);
+        self.color.record(color_us);
+        self.transmit.record(transmit_us);
+        self.update.samples >= self.report_interval_frames
+    }
+
+    /// Starts a new reporting window.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.report_interval_frames);
+    }
+}
+
+impl fmt::Display for Telemetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "update: {} | color: {} | transmit: {} | dropped: {}/{} frames",
+            self.update, self.color, self.transmit, self.dropped_frames, self.update.samples
+        )?;
+        write!(
+            f,
+            " | skipped writes: {} | write failures: {} | reinits: {}",
+            self.skipped_writes, self.write_failures, self.reinits
+        )
+    }
+}
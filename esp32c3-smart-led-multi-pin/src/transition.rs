@@ -0,0 +1,90 @@
+//! Crossfades between two rendered states.
+//!
+//! When the scene changes, the render loop keeps rendering the old state and starts rendering
+//! the new one next to it, then hands the output stage a blend of the two. A `Crossfade` tracks
+//! how far along the blend is, and `blend` mixes the two logical color buffers.
+
+use rgb::RGB8;
+
+/// Blend amount at which the new state has fully replaced the old one.
+pub const FULL_BLEND: u16 = u16::MAX;
+
+/// How the blend amount moves from old to new over the crossfade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    /// Constant speed the whole way through.
+    Linear,
+    /// Starts and ends slowly, so the change has no visible start or end point.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps linear progress through the crossfade to a blend amount, both out of `FULL_BLEND`.
+    pub const fn apply(&self, progress: u16) -> u16 {
+        match self {
+            Easing::Linear => progress,
+            Easing::EaseInOut => {
+                // Smoothstep, 3t^2 - 2t^3.
+                let t = progress as u64;
+                let full = FULL_BLEND as u64;
+                (t * t * (3 * full - 2 * t) / (full * full)) as u16
+            }
+        }
+    }
+}
+
+/// One crossfade in progress.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Crossfade {
+    start_us: u64,
+    duration_us: u64,
+    easing: Easing,
+}
+
+impl Crossfade {
+    /// Starts a crossfade at `start_us` that takes `duration_us` to complete.
+    pub const fn new(start_us: u64, duration_us: u64, easing: Easing) -> Self {
+        Crossfade { start_us, duration_us, easing }
+    }
+
+    /// How far the new state should be blended in at `now_us`, out of `FULL_BLEND`.
+    pub fn amount(&self, now_us: u64) -> u16 {
+        let elapsed_us = now_us.saturating_sub(self.start_us);
+        let progress = match (elapsed_us * FULL_BLEND as u64).checked_div(self.duration_us) {
+            Some(progress) => progress.min(FULL_BLEND as u64) as u16,
+            None => FULL_BLEND,
+        };
+        self.easing.apply(progress)
+    }
+
+    /// Returns `true` once the new state has fully replaced the old one.
+    pub fn is_finished(&self, now_us: u64) -> bool {
+        now_us.saturating_sub(self.start_us) >= self.duration_us
+    }
+}
+
+/// Writes the mix of `from` and `to` into `output`. An `amount` of 0 gives `from`, `FULL_BLEND`
+/// gives `to`.
+pub fn blend(from: &[RGB8], to: &[RGB8], amount: u16, output: &mut [RGB8]) {
+    let amount = amount as u32;
+    let mix = |a: u8, b: u8| {
+        let a = a as u32 * (FULL_BLEND as u32 - amount);
+        let b = b as u32 * amount;
+        ((a + b + FULL_BLEND as u32 / 2) / FULL_BLEND as u32) as u8
+    };
+    for ((output, from), to) in output.iter_mut().zip(from).zip(to) {
+        *output = RGB8 {
+            r: mix(from.r, to.r),
+            g: mix(from.g, to.g),
+            b: mix(from.b, to.b),
+        };
+    }
+}
+
+/// Where a background that takes `duration_ns` per cycle is at `now_us`, out of `u16::MAX` like
+/// `lighting_controller`'s offsets. Setting both copies of a background from this puts the one
+/// about to fade in exactly where the shown one is, however long it sat idle.
+pub fn background_offset(now_us: u64, duration_ns: u64) -> u16 {
+    let duration_us = (duration_ns / 1_000).max(1);
+    ((now_us % duration_us) * u16::MAX as u64 / duration_us) as u16
+}
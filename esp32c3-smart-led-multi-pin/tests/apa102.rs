@@ -0,0 +1,163 @@
+use embassy_futures::block_on;
+use esp32c3_smart_led_multi_pin::apa102::{
+    Apa102Adapter, Apa102Error, Apa102Led, MAX_GLOBAL_BRIGHTNESS, SpiWrite, encode_frame,
+    end_frame_len, frame_len,
+};
+use rgb::{RGB8, RGB16};
+use smart_leds::SmartLedsWriteAsync;
+
+const FULL_SCALE: u16 = 255 << 8;
+
+#[test]
+fn led_frames_are_brightness_then_blue_green_red() {
+    let led = Apa102Led::new(RGB8::new(1, 2, 3), 5);
+    assert_eq!(led.to_bytes(), [0b1110_0101, 3, 2, 1]);
+    // Brightness past the 5-bit field is clamped rather than spilling into the marker bits.
+    let led = Apa102Led::new(RGB8::new(1, 2, 3), 40);
+    assert_eq!(led.to_bytes(), [0b1111_1111, 3, 2, 1]);
+}
+
+#[test]
+fn end_frame_has_a_clock_for_every_two_leds() {
+    // The 4 byte reset frame, then the extra clocks rounded up to whole bytes.
+    assert_eq!(end_frame_len(0), 4);
+    assert_eq!(end_frame_len(1), 5);
+    assert_eq!(end_frame_len(16), 5);
+    assert_eq!(end_frame_len(17), 6);
+    assert_eq!(end_frame_len(32), 6);
+    assert_eq!(end_frame_len(33), 7);
+    assert_eq!(frame_len(2), 4 + 2 * 4 + 5);
+}
+
+#[test]
+fn frame_layout() {
+    let leds = [
+        Apa102Led::new(RGB8::new(1, 2, 3), 31),
+        Apa102Led::new(RGB8::new(4, 5, 6), 0),
+    ];
+    // Anything past the frame is left alone.
+    let mut buffer = [0xaa; 20];
+    assert_eq!(encode_frame(leds, &mut buffer), Some(frame_len(2)));
+    assert_eq!(
+        buffer,
+        [
+            0, 0, 0, 0, // start frame
+            0xff, 3, 2, 1, // first LED
+            0xe0, 6, 5, 4, // second LED
+            0, 0, 0, 0, 0, // end frame
+            0xaa, 0xaa, 0xaa,
+        ]
+    );
+}
+
+#[test]
+fn buffer_too_small() {
+    let leds = [Apa102Led::default(); 2];
+    let mut buffer = [0; frame_len(2)];
+    assert_eq!(encode_frame(leds, &mut buffer[..frame_len(2) - 1]), None);
+    // Short of room for the LED frames as well as the end frame.
+    assert_eq!(encode_frame(leds, &mut buffer[..6]), None);
+    assert_eq!(encode_frame(leds, &mut buffer[..2]), None);
+    assert_eq!(encode_frame(leds, &mut buffer), Some(frame_len(2)));
+}
+
+#[test]
+fn from_fixed_black_and_full_scale() {
+    assert_eq!(
+        Apa102Led::from_fixed(RGB16::default()),
+        Apa102Led::default()
+    );
+    let white = RGB16::new(FULL_SCALE, FULL_SCALE, FULL_SCALE);
+    assert_eq!(
+        Apa102Led::from_fixed(white),
+        Apa102Led::new(RGB8::new(255, 255, 255), MAX_GLOBAL_BRIGHTNESS)
+    );
+}
+
+#[test]
+fn from_fixed_uses_the_lowest_brightness_that_fits() {
+    // One 8-bit level is sent as 31 at the lowest brightness, 31 times finer than rounding.
+    let led = Apa102Led::from_fixed(RGB16::new(0x0100, 0x0080, 0));
+    assert_eq!(led, Apa102Led::new(RGB8::new(31, 16, 0), 1));
+
+    for high in (1..=FULL_SCALE).step_by(97) {
+        let color = RGB16::new(high / 3, high, high / 2);
+        let led = Apa102Led::from_fixed(color);
+        let brightness = led.brightness as u32;
+        assert!(brightness >= 1 && brightness <= MAX_GLOBAL_BRIGHTNESS as u32);
+        // A step lower and the brightest channel wouldn't fit in 8 bits.
+        assert!(
+            high as u32 * 31 > (brightness - 1) * FULL_SCALE as u32,
+            "{color:?}"
+        );
+
+        // Each channel is within half a step at that brightness of what was asked for.
+        let step = brightness as f64 * 256.0 / 31.0;
+        let sent = [led.color.r, led.color.g, led.color.b];
+        for (sent, wanted) in sent.into_iter().zip([color.r, color.g, color.b]) {
+            let error = (sent as f64 * step - wanted as f64).abs();
+            assert!(error <= step / 2.0 + 1.0, "{color:?} {led:?}");
+        }
+    }
+}
+
+// Keeps every transfer, or fails them all.
+#[derive(Default)]
+struct FakeSpi {
+    sent: Vec<Vec<u8>>,
+    fail: bool,
+}
+
+impl SpiWrite for &mut FakeSpi {
+    type Error = &'static str;
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.fail {
+            return Err("bus fault");
+        }
+        self.sent.push(bytes.to_vec());
+        Ok(())
+    }
+}
+
+#[test]
+fn adapter_sends_one_frame_per_write() {
+    let mut spi = FakeSpi::default();
+    let mut buffer = [0; frame_len(3)];
+    let mut adapter = Apa102Adapter::new(&mut spi, &mut buffer);
+    adapter.set_brightness(40);
+    assert_eq!(adapter.brightness(), MAX_GLOBAL_BRIGHTNESS);
+    adapter.set_brightness(2);
+    let colors = [RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)];
+    assert_eq!(block_on(adapter.write(colors)), Ok(()));
+    // Only as much of the buffer as the frame takes is sent.
+    assert_eq!(
+        spi.sent,
+        [vec![
+            0, 0, 0, 0, // start frame
+            0xe2, 3, 2, 1, // first LED
+            0xe2, 6, 5, 4, // second LED
+            0, 0, 0, 0, 0, // end frame
+        ]]
+    );
+}
+
+#[test]
+fn adapter_errors() {
+    let mut spi = FakeSpi::default();
+    let mut buffer = [0; frame_len(1)];
+    let mut adapter = Apa102Adapter::new(&mut spi, &mut buffer);
+    let colors = [RGB8::default(); 2];
+    assert_eq!(
+        block_on(adapter.write(colors)),
+        Err(Apa102Error::BufferTooSmall)
+    );
+    assert!(spi.sent.is_empty());
+
+    spi.fail = true;
+    let mut adapter = Apa102Adapter::new(&mut spi, &mut buffer);
+    assert_eq!(
+        block_on(adapter.write([RGB8::default()])),
+        Err(Apa102Error::Spi("bus fault"))
+    );
+}
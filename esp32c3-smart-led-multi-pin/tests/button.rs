@@ -0,0 +1,190 @@
+use esp32c3_smart_led_multi_pin::button::{BUTTON_DEFAULT, Button, Event};
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Time between polls, as if the input task woke up every millisecond.
+const STEP_US: u64 = 1_000;
+
+// Runs a button against a scripted pin. `script` lists the times the pressed state changes, and
+// the button is polled every `STEP_US` until `end_us`. Returns every event with its timestamp.
+fn run(pressed_at_boot: bool, script: &[(u64, bool)], end_us: u64) -> Vec<(u64, Event)> {
+    // The default config is active low, so the pin reads low while the button is down.
+    let level = Rc::new(Cell::new(!pressed_at_boot));
+    let pin = level.clone();
+    let mut button = Button::new(move || pin.get(), BUTTON_DEFAULT);
+    let mut events = Vec::new();
+    let mut now_us = 0;
+    while now_us <= end_us {
+        for &(at_us, pressed) in script {
+            if at_us == now_us {
+                level.set(!pressed);
+            }
+        }
+        while let Some(event) = button.update(now_us) {
+            events.push((now_us, event));
+        }
+        now_us += STEP_US;
+    }
+    events
+}
+
+fn kinds(events: &[(u64, Event)]) -> Vec<Event> {
+    events.iter().map(|&(_, event)| event).collect()
+}
+
+#[test]
+fn bounces_are_filtered() {
+    let script = [
+        (10_000, true),
+        (11_000, false),
+        (12_000, true),
+        (13_000, false),
+        (14_000, true),
+        (200_000, false),
+        (201_000, true),
+        (202_000, false),
+    ];
+    let events = run(false, &script, 1_000_000);
+    assert_eq!(
+        events,
+        [
+            (34_000, Event::Pressed),
+            (222_000, Event::Released),
+            (222_000, Event::Click),
+        ]
+    );
+}
+
+#[test]
+fn glitch_shorter_than_debounce_is_ignored() {
+    let events = run(false, &[(10_000, true), (25_000, false)], 1_000_000);
+    assert_eq!(events, []);
+}
+
+#[test]
+fn long_press_then_held_repeats() {
+    let events = run(false, &[(0, true), (1_500_000, false)], 2_000_000);
+    assert_eq!(
+        events,
+        [
+            (20_000, Event::Pressed),
+            (820_000, Event::LongPress),
+            (1_020_000, Event::Held),
+            (1_220_000, Event::Held),
+            (1_420_000, Event::Held),
+            (1_520_000, Event::Released),
+        ]
+    );
+}
+
+#[test]
+fn short_press_has_no_long_press() {
+    let events = run(false, &[(0, true), (700_000, false)], 2_000_000);
+    assert_eq!(
+        kinds(&events),
+        [Event::Pressed, Event::Released, Event::Click]
+    );
+}
+
+#[test]
+fn double_click() {
+    let script = [
+        (0, true),
+        (100_000, false),
+        (300_000, true),
+        (400_000, false),
+    ];
+    let events = run(false, &script, 1_000_000);
+    assert_eq!(
+        events,
+        [
+            (20_000, Event::Pressed),
+            (120_000, Event::Released),
+            (120_000, Event::Click),
+            (320_000, Event::Pressed),
+            (320_000, Event::DoubleClick),
+            (420_000, Event::Released),
+            (420_000, Event::Click),
+        ]
+    );
+}
+
+#[test]
+fn slow_second_click_is_not_a_double_click() {
+    let script = [
+        (0, true),
+        (100_000, false),
+        (500_000, true),
+        (600_000, false),
+    ];
+    let events = run(false, &script, 1_000_000);
+    assert!(!kinds(&events).contains(&Event::DoubleClick));
+}
+
+#[test]
+fn third_click_does_not_double_click_again() {
+    let script = [
+        (0, true),
+        (100_000, false),
+        (200_000, true),
+        (300_000, false),
+        (400_000, true),
+        (500_000, false),
+    ];
+    let events = run(false, &script, 1_000_000);
+    let doubles = kinds(&events)
+        .into_iter()
+        .filter(|&event| event == Event::DoubleClick)
+        .count();
+    assert_eq!(doubles, 1);
+}
+
+#[test]
+fn click_after_long_press_is_not_a_double_click() {
+    let script = [
+        (0, true),
+        (900_000, false),
+        (1_000_000, true),
+        (1_100_000, false),
+    ];
+    let events = run(false, &script, 2_000_000);
+    assert!(!kinds(&events).contains(&Event::DoubleClick));
+}
+
+#[test]
+fn pressed_at_boot_reports_only_the_release() {
+    let events = run(true, &[(2_000_000, false)], 3_000_000);
+    assert_eq!(events, [(2_020_000, Event::Released)]);
+}
+
+#[test]
+fn release_after_boot_press_does_not_arm_a_double_click() {
+    let events = run(true, &[(100_000, false), (200_000, true)], 1_000_000);
+    assert_eq!(kinds(&events), [Event::Released, Event::Pressed]);
+}
+
+#[test]
+fn deadlines_follow_the_state() {
+    let level = Rc::new(Cell::new(true));
+    let pin = level.clone();
+    let mut button = Button::new(move || pin.get(), BUTTON_DEFAULT);
+    assert_eq!(button.next_deadline_us(), None);
+
+    level.set(false);
+    assert_eq!(button.update(1_000), None);
+    assert_eq!(button.next_deadline_us(), Some(21_000));
+    assert_eq!(button.update(21_000), Some(Event::Pressed));
+    assert!(button.is_pressed());
+    assert_eq!(button.next_deadline_us(), Some(821_000));
+    assert_eq!(button.update(821_000), Some(Event::LongPress));
+    assert_eq!(button.next_deadline_us(), Some(1_021_000));
+}
+
+#[test]
+fn long_press_is_not_a_click() {
+    let events = run(false, &[(0, true), (900_000, false)], 2_000_000);
+    assert_eq!(
+        kinds(&events),
+        [Event::Pressed, Event::LongPress, Event::Released]
+    );
+}
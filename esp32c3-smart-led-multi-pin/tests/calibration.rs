@@ -0,0 +1,109 @@
+use esp32c3_smart_led_multi_pin::calibration::{
+    Calibration, CursorInfo, START_MARKER, WALL_COLORS, WALL_DIM_SHIFT,
+};
+use esp32c3_smart_led_multi_pin::layout::{Layout, Wall};
+use esp32c3_smart_led_multi_pin::output::{Output, OutputMap};
+use rgb::RGB8;
+use smart_leds::colors::*;
+
+// Three walls over two strips, the last one reversed.
+const LAYOUT: Layout<3, 2> = Layout::new([
+    Wall { output: 0, len: 4, reverse: false },
+    Wall { output: 0, len: 3, reverse: false },
+    Wall { output: 1, len: 5, reverse: true },
+]);
+const NUM_LEDS: usize = LAYOUT.num_leds();
+const OUTPUTS: OutputMap<2> = OutputMap::new([Output::rgb(0, 7), Output::rgb(7, 5)]);
+
+#[test]
+fn stepping_wraps_around_either_end() {
+    let mut calibration = Calibration::new();
+    assert_eq!(calibration.cursor(), 0);
+    calibration.step(-1, NUM_LEDS);
+    assert_eq!(calibration.cursor(), NUM_LEDS - 1);
+    calibration.step(3, NUM_LEDS);
+    assert_eq!(calibration.cursor(), 2);
+    calibration.step(-(NUM_LEDS as isize) * 2 - 1, NUM_LEDS);
+    assert_eq!(calibration.cursor(), 1);
+}
+
+#[test]
+fn next_wall_goes_to_each_start_marker_in_turn() {
+    let mut calibration = Calibration::new();
+    calibration.step(2, NUM_LEDS);
+    calibration.next_wall(&LAYOUT);
+    assert_eq!(calibration.cursor(), 4);
+    // A reversed wall's animation starts at its last LED in the logical buffer.
+    calibration.next_wall(&LAYOUT);
+    assert_eq!(calibration.cursor(), 11);
+    calibration.next_wall(&LAYOUT);
+    assert_eq!(calibration.cursor(), 0);
+}
+
+#[test]
+fn next_wall_from_a_reversed_start_marker() {
+    let mut calibration = Calibration::new();
+    calibration.step(-1, NUM_LEDS);
+    calibration.next_wall(&LAYOUT);
+    assert_eq!(calibration.cursor(), 0);
+}
+
+#[test]
+fn cursor_info_counts_reversed_walls_from_their_start_marker() {
+    let mut calibration = Calibration::new();
+    calibration.step(5, NUM_LEDS);
+    assert_eq!(
+        calibration.cursor_info(&LAYOUT, &OUTPUTS),
+        CursorInfo { logical: 5, wall: 1, wall_pixel: 1, physical: Some((0, 5)) }
+    );
+    calibration.step(5, NUM_LEDS);
+    assert_eq!(
+        calibration.cursor_info(&LAYOUT, &OUTPUTS),
+        CursorInfo { logical: 10, wall: 2, wall_pixel: 1, physical: Some((1, 3)) }
+    );
+}
+
+#[test]
+fn render_marks_both_ends_of_every_wall() {
+    let dim = |color: RGB8| RGB8 {
+        r: color.r >> WALL_DIM_SHIFT,
+        g: color.g >> WALL_DIM_SHIFT,
+        b: color.b >> WALL_DIM_SHIFT,
+    };
+    let mut calibration = Calibration::new();
+    calibration.step(5, NUM_LEDS);
+    let mut buffer = [BLACK; NUM_LEDS];
+    calibration.render(&LAYOUT, &mut buffer, 0);
+    assert_eq!(
+        buffer,
+        [
+            START_MARKER,
+            dim(WALL_COLORS[0]),
+            dim(WALL_COLORS[0]),
+            WALL_COLORS[0],
+            START_MARKER,
+            WHITE,
+            WALL_COLORS[1],
+            WALL_COLORS[2],
+            dim(WALL_COLORS[2]),
+            dim(WALL_COLORS[2]),
+            dim(WALL_COLORS[2]),
+            START_MARKER,
+        ]
+    );
+}
+
+#[test]
+fn cursor_blinks() {
+    let mut calibration = Calibration::new();
+    calibration.step(5, NUM_LEDS);
+    let cursor_at = |now_us| {
+        let mut buffer = [BLACK; NUM_LEDS];
+        calibration.render(&LAYOUT, &mut buffer, now_us);
+        buffer[5]
+    };
+    assert_eq!(cursor_at(0), WHITE);
+    assert_eq!(cursor_at(249_999), WHITE);
+    assert_eq!(cursor_at(250_000), BLACK);
+    assert_eq!(cursor_at(500_000), WHITE);
+}
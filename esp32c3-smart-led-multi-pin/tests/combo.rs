@@ -0,0 +1,168 @@
+use esp32c3_smart_led_multi_pin::button::Event;
+use esp32c3_smart_led_multi_pin::combo::{Action, Combo};
+use esp32c3_smart_led_multi_pin::input::InputEvent;
+
+const WINDOW_US: u64 = 150_000;
+
+// Feeds `script` of (time, button, event) to a combo of buttons 0 and 2, polling it like the
+// control task does: after every input, and at the deadline if nothing comes in before it. Returns
+// every action with the time it was taken.
+fn run(script: &[(u64, u8, Event)]) -> Vec<(u64, Action)> {
+    let mut combo = Combo::new([0, 2], WINDOW_US);
+    let mut actions = Vec::new();
+    let mut drain = |combo: &mut Combo, now_us: u64| {
+        while let Some(action) = combo.update(now_us) {
+            actions.push((now_us, action));
+        }
+    };
+    for &(at_us, id, event) in script {
+        if let Some(deadline_us) = combo.next_deadline_us().filter(|&due| due < at_us) {
+            drain(&mut combo, deadline_us);
+        }
+        combo.input(InputEvent::Button { id, event }, at_us);
+        drain(&mut combo, at_us);
+    }
+    if let Some(deadline_us) = combo.next_deadline_us() {
+        drain(&mut combo, deadline_us);
+    }
+    actions
+}
+
+const fn input(id: u8, event: Event) -> Action {
+    Action::Input(InputEvent::Button { id, event })
+}
+
+#[test]
+fn other_buttons_pass_straight_through() {
+    let script = [
+        (0, 1, Event::Pressed),
+        (800_000, 1, Event::LongPress),
+        (900_000, 1, Event::Released),
+    ];
+    let actions = run(&script);
+    let expected: Vec<_> = script
+        .iter()
+        .map(|&(at, id, event)| (at, input(id, event)))
+        .collect();
+    assert_eq!(actions, expected);
+}
+
+#[test]
+fn quick_click_is_let_through_on_release() {
+    let actions = run(&[(0, 0, Event::Pressed), (100_000, 0, Event::Released)]);
+    assert_eq!(
+        actions,
+        [
+            (100_000, input(0, Event::Pressed)),
+            (100_000, input(0, Event::Released)),
+        ]
+    );
+}
+
+#[test]
+fn held_press_is_let_through_when_the_window_ends() {
+    let actions = run(&[
+        (0, 2, Event::Pressed),
+        (800_000, 2, Event::LongPress),
+        (1_000_000, 2, Event::Released),
+    ]);
+    assert_eq!(
+        actions,
+        [
+            (WINDOW_US, input(2, Event::Pressed)),
+            (800_000, input(2, Event::LongPress)),
+            (1_000_000, input(2, Event::Released)),
+        ]
+    );
+}
+
+#[test]
+fn combo_replaces_both_presses() {
+    let actions = run(&[
+        (0, 0, Event::Pressed),
+        (50_000, 2, Event::Pressed),
+        (300_000, 0, Event::Released),
+        (300_000, 0, Event::Click),
+        (350_000, 2, Event::Released),
+        (350_000, 2, Event::Click),
+    ]);
+    assert_eq!(actions, [(50_000, Action::Combo)]);
+}
+
+#[test]
+fn long_presses_and_repeats_after_the_combo_are_dropped() {
+    let actions = run(&[
+        (0, 2, Event::Pressed),
+        (100_000, 0, Event::Pressed),
+        (800_000, 2, Event::LongPress),
+        (900_000, 0, Event::LongPress),
+        (1_000_000, 2, Event::Held),
+        (1_100_000, 2, Event::Released),
+        (1_200_000, 0, Event::Held),
+        (1_300_000, 0, Event::Released),
+        // Once released, the buttons work on their own again.
+        (2_000_000, 2, Event::Pressed),
+        (2_050_000, 2, Event::Released),
+        (2_050_000, 2, Event::Click),
+    ]);
+    assert_eq!(
+        actions,
+        [
+            (100_000, Action::Combo),
+            (2_050_000, input(2, Event::Pressed)),
+            (2_050_000, input(2, Event::Released)),
+            (2_050_000, input(2, Event::Click)),
+        ]
+    );
+}
+
+#[test]
+fn presses_further_apart_than_the_window_are_separate() {
+    let actions = run(&[
+        (0, 0, Event::Pressed),
+        (200_000, 2, Event::Pressed),
+        (250_000, 2, Event::Released),
+        (300_000, 0, Event::Released),
+    ]);
+    assert_eq!(
+        actions,
+        [
+            (WINDOW_US, input(0, Event::Pressed)),
+            (250_000, input(2, Event::Pressed)),
+            (250_000, input(2, Event::Released)),
+            (300_000, input(0, Event::Released)),
+        ]
+    );
+}
+
+#[test]
+fn other_buttons_do_not_wait_for_a_held_back_press() {
+    let actions = run(&[
+        (0, 0, Event::Pressed),
+        (50_000, 1, Event::Pressed),
+        (100_000, 0, Event::Released),
+    ]);
+    assert_eq!(
+        actions,
+        [
+            (50_000, input(1, Event::Pressed)),
+            (100_000, input(0, Event::Pressed)),
+            (100_000, input(0, Event::Released)),
+        ]
+    );
+}
+
+#[test]
+fn late_input_lets_the_held_back_press_through_first() {
+    // An input arriving after the deadline, before the combo was polled for it.
+    let mut combo = Combo::new([0, 2], WINDOW_US);
+    combo.input(InputEvent::Button { id: 0, event: Event::Pressed }, 0);
+    assert_eq!(combo.update(0), None);
+    combo.input(
+        InputEvent::Button { id: 2, event: Event::Pressed },
+        WINDOW_US,
+    );
+    assert_eq!(combo.update(WINDOW_US), Some(input(0, Event::Pressed)));
+    assert_eq!(combo.update(WINDOW_US), None);
+    assert_eq!(combo.next_deadline_us(), Some(2 * WINDOW_US));
+}
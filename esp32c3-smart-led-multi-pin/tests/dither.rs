@@ -0,0 +1,116 @@
+use esp32c3_smart_led_multi_pin::correction::{ColorCorrection, CorrectionZone, NO_CORRECTION};
+use esp32c3_smart_led_multi_pin::dither::{TemporalDither, dither_outputs};
+use esp32c3_smart_led_multi_pin::gamma::GammaLut;
+use esp32c3_smart_led_multi_pin::output::{Output, OutputMap};
+use esp32c3_smart_led_multi_pin::power::{PowerLimiter, PowerModel, Supply, SupplyEstimate};
+use rgb::{RGB8, RGB16};
+
+// Enough frames for the carried error to go all the way round.
+const FRAMES: u64 = 256;
+
+// Sums what `dither` sends for LED 0 over `FRAMES` frames of the 8.8 value `target`.
+fn dithered_sum(dither: &mut TemporalDither<1>, target: u16) -> u64 {
+    (0..FRAMES)
+        .map(|_| dither.quantize(0, RGB16 { r: target, g: 0, b: 0 }).r as u64)
+        .sum()
+}
+
+#[test]
+fn time_average_matches_the_target() {
+    let mut dither = TemporalDither::<1>::new();
+    for target in [0, 1, 77, 128, 300, 0x0140, 1_000, 0x1234, 65_000, 65_280] {
+        dither.reset();
+        // The sum is off by less than one 8-bit level over the whole run.
+        let error = (dithered_sum(&mut dither, target) * 256).abs_diff(FRAMES * target as u64);
+        assert!(
+            error < 256,
+            "{target:#06x} is off by {error}/256 of a level"
+        );
+    }
+}
+
+#[test]
+fn full_scale_stays_at_full_brightness() {
+    let mut dither = TemporalDither::<1>::new();
+    assert_eq!(dithered_sum(&mut dither, u16::MAX), FRAMES * 255);
+}
+
+#[test]
+fn only_neighbouring_levels_are_sent() {
+    let mut dither = TemporalDither::<1>::new();
+    let levels: Vec<u8> = (0..8)
+        .map(|_| dither.quantize(0, RGB16 { r: 0x0140, g: 0x0200, b: 0 }))
+        .map(|color| {
+            assert_eq!(color.g, 2);
+            assert_eq!(color.b, 0);
+            color.r
+        })
+        .collect();
+    assert_eq!(levels, [1, 1, 1, 2, 1, 1, 1, 2]);
+}
+
+#[test]
+fn reset_forgets_the_carried_error() {
+    let mut dither = TemporalDither::<1>::new();
+    let quantize = |dither: &mut TemporalDither<1>| dither.quantize(0, RGB16::new(0x0080, 0, 0)).r;
+    assert_eq!(quantize(&mut dither), 0);
+    dither.reset();
+    assert_eq!(quantize(&mut dither), 0);
+    assert_eq!(quantize(&mut dither), 1);
+}
+
+const fn output(start: usize, dither: bool) -> Output {
+    Output { dither, ..Output::rgb(start, 2) }
+}
+
+#[test]
+fn dithering_comes_after_corrections_and_power_limit() {
+    const DIM: ColorCorrection = ColorCorrection { brightness: 100, r: 255, g: 200, b: 255 };
+    let outputs = OutputMap::new([output(0, true), output(2, false)]);
+    let luts = [GammaLut::new(2.2); 2];
+    let zones = [
+        CorrectionZone { start: 0, len: 1, correction: DIM },
+        CorrectionZone { start: 1, len: 3, correction: NO_CORRECTION },
+    ];
+    let model = PowerModel { channel_full_ua: 20_000, idle_ua: 1_000 };
+    let limiter = PowerLimiter::new(model, [Supply { outputs: &[0, 1], budget_ma: 100 }]);
+    let estimates = [SupplyEstimate { requested_ma: 150, delivered_ma: 100, scale: 160 }];
+    let colors = [RGB8::new(40, 90, 0); 4];
+
+    // The non-dithered output keeps whatever the 8-bit pass left in the buffer.
+    let passed = RGB8::new(7, 8, 9);
+    let mut dither = TemporalDither::<4>::new();
+    let mut sums = [[0_u64; 2]; 2];
+    for _ in 0..FRAMES {
+        let mut buffer = [passed; 4];
+        dither_outputs(
+            &outputs,
+            &luts,
+            &zones,
+            &limiter,
+            &estimates,
+            &colors,
+            &mut buffer,
+            &mut dither,
+        );
+        assert_eq!(buffer[2..], [passed; 2]);
+        for (sum, color) in sums.iter_mut().zip(&buffer) {
+            sum[0] += color.r as u64;
+            sum[1] += color.g as u64;
+        }
+    }
+
+    for (led, sum) in sums.iter().enumerate() {
+        let correction = zones[led].correction;
+        let fixed = correction.apply_fixed(RGB16 {
+            r: luts[0].apply_fixed(colors[led].r),
+            g: luts[0].apply_fixed(colors[led].g),
+            b: 0,
+        });
+        for (sum, target) in sum.iter().zip([fixed.r, fixed.g]) {
+            let target = target as u64 * 160 / 256;
+            let error = (sum * 256).abs_diff(FRAMES * target);
+            assert!(error < 256, "LED {led} is off by {error}/256 of a level");
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use esp32c3_smart_led_multi_pin::error::{
+    OutputError, REINIT_AFTER_FAILURES, WriteAction, WriteHealth,
+};
+
+const OK: Result<(), ()> = Ok(());
+const FAILED: Result<(), ()> = Err(());
+
+#[test]
+fn successful_writes_continue() {
+    let mut health = WriteHealth::default();
+    for _ in 0..3 {
+        assert_eq!(health.record(&OK), WriteAction::Continue);
+    }
+    assert_eq!(health.consecutive_failures, 0);
+}
+
+#[test]
+fn only_the_first_failure_in_a_row_is_reported() {
+    let mut health = WriteHealth::default();
+    assert_eq!(health.record(&FAILED), WriteAction::Report);
+    assert_eq!(health.record(&FAILED), WriteAction::Continue);
+    assert_eq!(health.record(&FAILED), WriteAction::Continue);
+    assert_eq!(health.consecutive_failures, 3);
+}
+
+#[test]
+fn a_successful_write_resets_the_count() {
+    let mut health = WriteHealth::default();
+    health.record(&FAILED);
+    health.record(&FAILED);
+    assert_eq!(health.record(&OK), WriteAction::Continue);
+    assert_eq!(health.consecutive_failures, 0);
+    // The strip failing again is news worth logging.
+    assert_eq!(health.record(&FAILED), WriteAction::Report);
+    assert_eq!(health.consecutive_failures, 1);
+}
+
+#[test]
+fn a_strip_that_keeps_failing_is_reinitialized() {
+    let mut health = WriteHealth::default();
+    assert_eq!(health.record(&FAILED), WriteAction::Report);
+    for _ in 2..REINIT_AFTER_FAILURES {
+        assert_eq!(health.record(&FAILED), WriteAction::Continue);
+    }
+    assert_eq!(health.record(&FAILED), WriteAction::Reinit);
+    assert_eq!(health.consecutive_failures, 0);
+    // Still failing after the reinit: reported once more, then set up again after as many
+    // failures again.
+    assert_eq!(health.record(&FAILED), WriteAction::Report);
+    for _ in 2..REINIT_AFTER_FAILURES {
+        assert_eq!(health.record(&FAILED), WriteAction::Continue);
+    }
+    assert_eq!(health.record(&FAILED), WriteAction::Reinit);
+}
+
+#[test]
+fn errors_name_their_output() {
+    let error = OutputError::new(1, "timeout");
+    assert_eq!(error.to_string(), "write to output 1 failed: \"timeout\"");
+}
@@ -0,0 +1,79 @@
+use esp32c3_smart_led_multi_pin::frame_scheduler::{FrameScheduler, FrameStats};
+
+#[test]
+fn period_is_rounded_to_the_nearest_microsecond() {
+    assert_eq!(FrameScheduler::new(60).period_us(), 16_667);
+    assert_eq!(FrameScheduler::new(30).period_us(), 33_333);
+    assert_eq!(FrameScheduler::new(1).period_us(), 1_000_000);
+    assert_eq!(FrameScheduler::new(60).frame_rate().0, 60);
+}
+
+#[test]
+fn frames_on_time() {
+    let mut scheduler = FrameScheduler::new(100);
+    let mut now_us = 1_000;
+    for _ in 0..10 {
+        scheduler.start_frame(now_us);
+        scheduler.end_frame(now_us + 4_000);
+        now_us = scheduler.next_frame_due_us();
+    }
+    assert_eq!(now_us, 101_000);
+    assert_eq!(
+        scheduler.stats(),
+        FrameStats {
+            frames: 10,
+            missed_deadlines: 0,
+            max_jitter_us: 0,
+            worst_render_us: 4_000,
+            total_render_us: 40_000,
+        }
+    );
+}
+
+#[test]
+fn late_wakeups_are_jitter_but_stay_on_schedule() {
+    let mut scheduler = FrameScheduler::new(100);
+    scheduler.start_frame(0);
+    scheduler.end_frame(1_000);
+    // Woken 300us late, the next frame is still due on the original grid.
+    scheduler.start_frame(10_300);
+    scheduler.end_frame(11_000);
+    assert_eq!(scheduler.next_frame_due_us(), 20_000);
+    scheduler.start_frame(20_100);
+    scheduler.end_frame(21_000);
+
+    let stats = scheduler.stats();
+    assert_eq!(stats.max_jitter_us, 300);
+    assert_eq!(stats.missed_deadlines, 0);
+}
+
+#[test]
+fn missed_deadline_reschedules_from_now() {
+    let mut scheduler = FrameScheduler::new(100);
+    scheduler.start_frame(0);
+    // Still inside the next frame's slot, so nothing was missed.
+    scheduler.end_frame(10_000);
+    assert_eq!(scheduler.stats().missed_deadlines, 0);
+    assert_eq!(scheduler.next_frame_due_us(), 10_000);
+
+    scheduler.start_frame(10_000);
+    scheduler.end_frame(35_000);
+    let stats = scheduler.stats();
+    assert_eq!(stats.missed_deadlines, 1);
+    assert_eq!(stats.worst_render_us, 25_000);
+    // No burst of catch-up frames, the next one starts right away and the grid moves.
+    assert_eq!(scheduler.next_frame_due_us(), 35_000);
+    scheduler.start_frame(35_000);
+    assert_eq!(scheduler.next_frame_due_us(), 45_000);
+    assert_eq!(scheduler.stats().max_jitter_us, 0);
+}
+
+#[test]
+fn reset_stats_keeps_the_schedule() {
+    let mut scheduler = FrameScheduler::new(100);
+    scheduler.start_frame(0);
+    scheduler.end_frame(50_000);
+    scheduler.reset_stats();
+    assert_eq!(scheduler.stats(), FrameStats::default());
+    assert_eq!(scheduler.next_frame_due_us(), 50_000);
+}
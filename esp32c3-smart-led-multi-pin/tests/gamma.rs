@@ -0,0 +1,21 @@
+use esp32c3_smart_led_multi_pin::gamma::{GAMMA_2_2, GAMMA_2_8};
+use rgb::RGB8;
+
+#[test]
+fn gamma_2_8_matches_smart_leds() {
+    for value in 0..=255 {
+        let color = RGB8::new(value, value, value);
+        let expected = smart_leds::gamma(core::iter::once(color)).next().unwrap();
+        assert_eq!(GAMMA_2_8.apply_color(color), expected, "at {value}");
+    }
+}
+
+#[test]
+fn gamma_2_2() {
+    // `(value / 255)^2.2 * 255`, rounded.
+    assert_eq!(GAMMA_2_2.apply(0), 0);
+    assert_eq!(GAMMA_2_2.apply(64), 12);
+    assert_eq!(GAMMA_2_2.apply(128), 56);
+    assert_eq!(GAMMA_2_2.apply(200), 149);
+    assert_eq!(GAMMA_2_2.apply(255), 255);
+}
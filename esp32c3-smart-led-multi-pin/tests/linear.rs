@@ -0,0 +1,143 @@
+use esp32c3_smart_led_multi_pin::correction::{ColorCorrection, CorrectionZone, apply_corrections};
+use esp32c3_smart_led_multi_pin::default_consts::TWELVE_BIT_OKLCH_RAINBOW;
+use esp32c3_smart_led_multi_pin::dither::TemporalDither;
+use esp32c3_smart_led_multi_pin::gamma::{GammaLut, apply_luts};
+use esp32c3_smart_led_multi_pin::linear::{FULL_SCALE, LinearBuffer};
+use esp32c3_smart_led_multi_pin::output::{Output, OutputMap};
+use esp32c3_smart_led_multi_pin::power::{PowerLimiter, PowerModel, Supply};
+use rgb::{RGB8, RGB16};
+
+// Colors between each pair of neighbouring palette entries, like a slow background fade.
+const STEPS: usize = 16;
+const LEN: usize = (TWELVE_BIT_OKLCH_RAINBOW.len() - 1) * STEPS;
+
+const OUTPUTS: OutputMap<1> = OutputMap::new([Output::rgb(0, LEN)]);
+const LUTS: [GammaLut; 1] = [GammaLut::new(2.2)];
+// A warm white balance, dimmed down to night levels.
+const NIGHT: ColorCorrection = ColorCorrection { brightness: 32, r: 255, g: 230, b: 200 };
+const ZONES: [CorrectionZone; 1] = [CorrectionZone { start: 0, len: LEN, correction: NIGHT }];
+
+fn palette_fade() -> Vec<RGB8> {
+    let mix = |from: u8, to: u8, step: usize| {
+        ((from as usize * (STEPS - step) + to as usize * step + STEPS / 2) / STEPS) as u8
+    };
+    TWELVE_BIT_OKLCH_RAINBOW
+        .windows(2)
+        .flat_map(|pair| {
+            let (from, to) = (pair[0], pair[1]);
+            (0..STEPS).map(move |step| {
+                RGB8::new(
+                    mix(from.r, to.r, step),
+                    mix(from.g, to.g, step),
+                    mix(from.b, to.b, step),
+                )
+            })
+        })
+        .collect()
+}
+
+// Counts the channels in `output` that aren't the closest 8-bit level to what `colors` should
+// show after the gamma table, the night correction and a power limit of `scale / 256`.
+fn off_level_channels(colors: &[RGB8], output: &[RGB8], scale: u16) -> usize {
+    let exact = |channel: u8, factor: u8| {
+        let fixed = LUTS[0].apply_fixed(channel) as f64 / 256.0;
+        fixed * (NIGHT.brightness as f64 / 255.0) * (factor as f64 / 255.0) * (scale as f64 / 256.0)
+    };
+    colors
+        .iter()
+        .zip(output)
+        .flat_map(|(color, out)| {
+            [
+                (out.r, exact(color.r, NIGHT.r)),
+                (out.g, exact(color.g, NIGHT.g)),
+                (out.b, exact(color.b, NIGHT.b)),
+            ]
+        })
+        .filter(|&(out, exact)| (out as f64 - exact).abs() > 0.5 + 1.0 / 256.0)
+        .count()
+}
+
+// Runs `colors` through the default 8-bit output stage, returning the output and power scale.
+fn eight_bit(colors: &[RGB8], limiter: &PowerLimiter<1>) -> (Vec<RGB8>, u16) {
+    let mut output = vec![RGB8::default(); LEN];
+    apply_luts(&OUTPUTS, &LUTS, colors, &mut output);
+    apply_corrections(&ZONES, &mut output);
+    let [estimate] = limiter.limit(&OUTPUTS, &mut output);
+    (output, estimate.scale)
+}
+
+// Runs `colors` through the linear output stage, returning the output and power scale.
+fn linear(colors: &[RGB8], limiter: &PowerLimiter<1>) -> (Vec<RGB8>, u16) {
+    let mut buffer = LinearBuffer::<LEN>::new();
+    buffer.decode(&OUTPUTS, &LUTS, colors);
+    buffer.apply_corrections(&ZONES);
+    let [estimate] = limiter.limit_linear(&OUTPUTS, &mut buffer);
+    let mut output = vec![RGB8::default(); LEN];
+    buffer.quantize(&OUTPUTS, &mut output, &mut TemporalDither::new());
+    (output, estimate.scale)
+}
+
+fn limiter(budget_ma: u32) -> PowerLimiter<1> {
+    let model = PowerModel { channel_full_ua: 20_000, idle_ua: 0 };
+    PowerLimiter::new(model, [Supply { outputs: &[0], budget_ma }])
+}
+
+#[test]
+fn dimmed_rainbow_only_rounds_once() {
+    let colors = palette_fade();
+    let unlimited = limiter(100_000);
+
+    let (output, scale) = linear(&colors, &unlimited);
+    assert_eq!(scale, 256);
+    assert_eq!(off_level_channels(&colors, &output, scale), 0);
+
+    // Rounding after the gamma table and again after the correction puts some LEDs a level out.
+    let (output, scale) = eight_bit(&colors, &unlimited);
+    assert!(off_level_channels(&colors, &output, scale) > 10);
+}
+
+#[test]
+fn power_limited_rainbow_only_rounds_once() {
+    let colors = palette_fade();
+    let limited = limiter(1_500);
+
+    let (output, scale) = linear(&colors, &limited);
+    assert!(scale < 256);
+    assert_eq!(off_level_channels(&colors, &output, scale), 0);
+
+    // With a third rounding step most of the fade is off.
+    let (output, scale) = eight_bit(&colors, &limited);
+    assert!(scale < 256);
+    assert!(off_level_channels(&colors, &output, scale) > LEN);
+}
+
+#[test]
+fn crossfade_blends_at_full_precision() {
+    let mut buffer = LinearBuffer::<2>::new();
+    buffer.colors = [RGB16::new(0, 0x0100, FULL_SCALE), RGB16::new(0x0180, 0, 0)];
+    let target = [RGB16::new(FULL_SCALE, 0x0100, 0), RGB16::new(0x0181, 0, 0)];
+
+    let mut unchanged = LinearBuffer::<2>::new();
+    unchanged.colors = buffer.colors;
+    unchanged.crossfade(&target, 0);
+    assert_eq!(unchanged.colors, buffer.colors);
+
+    let mut halfway = LinearBuffer::<2>::new();
+    halfway.colors = buffer.colors;
+    halfway.crossfade(&target, u16::MAX / 2);
+    assert_eq!(halfway.colors[0], RGB16::new(0x7f80, 0x0100, 0x7f80));
+    assert_eq!(halfway.colors[1], RGB16::new(0x0180, 0, 0));
+
+    buffer.crossfade(&target, u16::MAX);
+    assert_eq!(buffer.colors, target);
+}
+
+#[test]
+fn scale_only_touches_its_range() {
+    let mut buffer = LinearBuffer::<3>::new();
+    buffer.colors = [RGB16::new(FULL_SCALE, 0x0100, 3); 3];
+    buffer.scale(1, 1, u16::MAX / 2);
+    assert_eq!(buffer.colors[0], RGB16::new(FULL_SCALE, 0x0100, 3));
+    assert_eq!(buffer.colors[1], RGB16::new(0x7f80, 0x0080, 1));
+    assert_eq!(buffer.colors[2], RGB16::new(FULL_SCALE, 0x0100, 3));
+}
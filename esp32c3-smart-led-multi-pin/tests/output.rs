@@ -0,0 +1,134 @@
+use esp32c3_smart_led_multi_pin::output::{ColorOrder, Output, OutputMap, PhysicalLed};
+use rgb::RGB8;
+
+// An RGB strip whose channels go out in the adapter's order, so colors come out unchanged.
+const fn output(start: usize, len: usize, reverse: bool) -> Output {
+    Output { reverse, ..Output::rgb(start, len) }
+}
+
+// A logical buffer where each color's red channel is its index.
+fn numbered(len: usize) -> Vec<RGB8> {
+    (0..len).map(|i| RGB8::new(i as u8, 0, 0)).collect()
+}
+
+fn reds(output: &Output, buffer: &[RGB8]) -> Vec<u8> {
+    output.colors(buffer).map(|color| color.r).collect()
+}
+
+#[test]
+fn outputs_show_their_slice() {
+    let map = OutputMap::new([output(0, 3, false), output(3, 4, true)]);
+    let buffer = numbered(map.total_len());
+    assert_eq!(map.total_len(), 7);
+    assert_eq!(reds(&map.outputs[0], &buffer), [0, 1, 2]);
+    assert_eq!(reds(&map.outputs[1], &buffer), [6, 5, 4, 3]);
+    assert_eq!(map.outputs[1].logical_index(0), Some(6));
+    assert_eq!(map.outputs[1].logical_index(3), Some(3));
+}
+
+#[test]
+fn outputs_can_be_listed_in_any_order() {
+    let map = OutputMap::new([output(4, 2, false), output(0, 4, false)]);
+    let buffer = numbered(map.total_len());
+    assert_eq!(map.total_len(), 6);
+    assert_eq!(reds(&map.outputs[0], &buffer), [4, 5]);
+    assert_eq!(map.physical_index(1), Some((1, 1)));
+    assert_eq!(map.physical_index(5), Some((0, 1)));
+}
+
+#[test]
+fn masked_and_bypassed_leds() {
+    // LED 1 is dead but still in the chain, and the LED for logical index 3 was cut out.
+    const LEDS: [PhysicalLed; 3] = [
+        PhysicalLed::Logical(2),
+        PhysicalLed::Black,
+        PhysicalLed::Logical(4),
+    ];
+    let masked = Output { leds: Some(&LEDS), ..output(2, 3, false) };
+    let map = OutputMap::new([output(0, 2, false), masked]);
+    let buffer: Vec<RGB8> = (0..map.total_len())
+        .map(|i| RGB8::new(i as u8, 1, 0))
+        .collect();
+    assert_eq!(masked.physical_len(), 3);
+    assert_eq!(masked.logical_index(1), None);
+    assert_eq!(
+        masked.colors(&buffer).collect::<Vec<_>>(),
+        [RGB8::new(2, 1, 0), RGB8::default(), RGB8::new(4, 1, 0)]
+    );
+    assert_eq!(map.physical_index(4), Some((1, 2)));
+    assert_eq!(map.physical_index(3), None);
+}
+
+#[test]
+fn single_output() {
+    let map = OutputMap::new([output(0, 5, true)]);
+    assert_eq!(map.total_len(), 5);
+    assert_eq!(reds(&map.outputs[0], &numbered(5)), [4, 3, 2, 1, 0]);
+    assert_eq!(map.physical_index(0), Some((0, 4)));
+}
+
+#[test]
+#[should_panic(expected = "Outputs must have at least one LED.")]
+fn empty_output_is_rejected() {
+    OutputMap::new([output(0, 2, false), output(2, 0, false)]);
+}
+
+#[test]
+#[should_panic(expected = "Outputs must not overlap in the logical buffer.")]
+fn overlapping_outputs_are_rejected() {
+    OutputMap::new([output(0, 3, false), output(2, 3, false)]);
+}
+
+#[test]
+#[should_panic(expected = "Outputs must cover the logical buffer without gaps.")]
+fn gaps_are_rejected() {
+    OutputMap::new([output(0, 2, false), output(3, 2, false)]);
+}
+
+#[test]
+#[should_panic(expected = "Output LEDs must show colors from the output's own slice.")]
+fn leds_outside_the_slice_are_rejected() {
+    const LEDS: [PhysicalLed; 2] = [PhysicalLed::Logical(0), PhysicalLed::Logical(1)];
+    let stray = Output { leds: Some(&LEDS), ..output(2, 2, false) };
+    OutputMap::new([output(0, 2, false), stray]);
+}
+
+// The channels of `color` in the order they go down the wire on a strip with channel order
+// `order`. The adapter sends each color's green, red and blue in that order.
+fn wire_order(order: ColorOrder, color: RGB8) -> [u8; 3] {
+    let strip = Output { order, ..output(0, 1, false) };
+    let sent = strip.colors(&[color]).next().unwrap();
+    [sent.g, sent.r, sent.b]
+}
+
+const RED_GREEN_BLUE: RGB8 = RGB8 { r: 1, g: 2, b: 3 };
+
+#[test]
+fn rgb_order() {
+    assert_eq!(wire_order(ColorOrder::Rgb, RED_GREEN_BLUE), [1, 2, 3]);
+}
+
+#[test]
+fn rbg_order() {
+    assert_eq!(wire_order(ColorOrder::Rbg, RED_GREEN_BLUE), [1, 3, 2]);
+}
+
+#[test]
+fn grb_order() {
+    assert_eq!(wire_order(ColorOrder::Grb, RED_GREEN_BLUE), [2, 1, 3]);
+}
+
+#[test]
+fn gbr_order() {
+    assert_eq!(wire_order(ColorOrder::Gbr, RED_GREEN_BLUE), [2, 3, 1]);
+}
+
+#[test]
+fn brg_order() {
+    assert_eq!(wire_order(ColorOrder::Brg, RED_GREEN_BLUE), [3, 1, 2]);
+}
+
+#[test]
+fn bgr_order() {
+    assert_eq!(wire_order(ColorOrder::Bgr, RED_GREEN_BLUE), [3, 2, 1]);
+}
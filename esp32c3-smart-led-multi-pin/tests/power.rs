@@ -0,0 +1,96 @@
+use esp32c3_smart_led_multi_pin::output::{Output, OutputMap, PhysicalLed};
+use esp32c3_smart_led_multi_pin::power::{PowerLimiter, PowerModel, Supply, SupplyEstimate};
+use rgb::RGB8;
+
+const MODEL: PowerModel = PowerModel { channel_full_ua: 20_000, idle_ua: 1_000 };
+const WHITE: RGB8 = RGB8 { r: 255, g: 255, b: 255 };
+
+// The second strip has two dead LEDs masked black at its end, and the LEDs for the last four
+// colors of its slice cut out.
+const LEDS: [PhysicalLed; 8] = [
+    PhysicalLed::Logical(10),
+    PhysicalLed::Logical(11),
+    PhysicalLed::Logical(12),
+    PhysicalLed::Logical(13),
+    PhysicalLed::Logical(14),
+    PhysicalLed::Logical(15),
+    PhysicalLed::Black,
+    PhysicalLed::Black,
+];
+
+const fn output(start: usize, leds: Option<&'static [PhysicalLed]>) -> Output {
+    Output { leds, ..Output::rgb(start, 10) }
+}
+
+const OUTPUTS: OutputMap<2> = OutputMap::new([output(0, None), output(10, Some(&LEDS))]);
+const LIMITER: PowerLimiter<2> = PowerLimiter::new(
+    MODEL,
+    [
+        Supply { outputs: &[0], budget_ma: 100 },
+        Supply { outputs: &[1], budget_ma: 1_000 },
+    ],
+);
+
+#[test]
+fn estimate_is_linear_in_duty() {
+    assert_eq!(MODEL.estimate_ua([RGB8::default()].into_iter()), 1_000);
+    assert_eq!(MODEL.estimate_ua([WHITE].into_iter()), 61_000);
+    assert_eq!(
+        MODEL.estimate_ua([RGB8::new(255, 0, 0); 4].into_iter()),
+        84_000
+    );
+    assert_eq!(MODEL.estimate_ua(core::iter::empty()), 0);
+}
+
+#[test]
+fn black_frame_draws_idle_current() {
+    let mut frame = [RGB8::default(); 20];
+    let estimates = LIMITER.limit(&OUTPUTS, &mut frame);
+    assert_eq!(
+        estimates,
+        [
+            SupplyEstimate { requested_ma: 10, delivered_ma: 10, scale: 256 },
+            SupplyEstimate { requested_ma: 8, delivered_ma: 8, scale: 256 },
+        ]
+    );
+}
+
+#[test]
+fn supply_over_budget_is_dimmed_to_fit() {
+    let mut frame = [WHITE; 20];
+    let [first, second] = LIMITER.limit(&OUTPUTS, &mut frame);
+    assert_eq!(first.requested_ma, 610);
+    assert!(first.scale < 256);
+    assert!(first.delivered_ma <= 100, "{first:?}");
+    assert!(first.delivered_ma >= 95, "{first:?}");
+    assert!(
+        frame[..10]
+            .iter()
+            .all(|&color| color == frame[0] && color.r < 40)
+    );
+
+    // The other supply has room to spare and is left alone.
+    assert_eq!(
+        second,
+        SupplyEstimate { requested_ma: 368, delivered_ma: 368, scale: 256 }
+    );
+    assert!(frame[10..].iter().all(|&color| color == WHITE));
+}
+
+#[test]
+fn cut_out_leds_draw_nothing() {
+    let mut frame = [RGB8::default(); 20];
+    frame[16..].fill(WHITE);
+    let [_, second] = LIMITER.limit(&OUTPUTS, &mut frame);
+    assert_eq!(second.requested_ma, 8);
+}
+
+#[test]
+fn budget_below_idle_current_blacks_out_the_supply() {
+    let limiter = PowerLimiter::new(MODEL, [Supply { outputs: &[0, 1], budget_ma: 5 }]);
+    let mut frame = [WHITE; 20];
+    let [estimate] = limiter.limit(&OUTPUTS, &mut frame);
+    assert_eq!(estimate.scale, 0);
+    assert_eq!(estimate.delivered_ma, 18);
+    assert!(frame.iter().all(|&color| color == RGB8::default()));
+}
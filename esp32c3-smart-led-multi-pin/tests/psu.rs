@@ -0,0 +1,94 @@
+use esp32c3_smart_led_multi_pin::psu::{PsuConfig, PsuControl, PsuState};
+
+const CONFIG: PsuConfig = PsuConfig {
+    off_after_black_us: 60_000_000,
+    settle_us: 50_000,
+    active_high: true,
+};
+const FRAME_US: u64 = 16_667;
+
+// When the supply switched on by `on` has settled.
+const SETTLED_US: u64 = 1_000 + CONFIG.settle_us;
+
+// A controller that has been switched on and has settled.
+fn on() -> PsuControl {
+    let mut psu = PsuControl::new(CONFIG);
+    psu.update(1_000, false);
+    assert!(psu.update(SETTLED_US, false));
+    psu
+}
+
+#[test]
+fn starts_off_and_stays_off_while_black() {
+    let mut psu = PsuControl::new(CONFIG);
+    assert_eq!(psu.state(), PsuState::Off);
+    assert!(!psu.supply_on());
+    assert!(!psu.pin_level());
+    for frame in 0..10 {
+        assert!(!psu.update(frame * FRAME_US, true));
+    }
+    assert_eq!(psu.state(), PsuState::Off);
+}
+
+#[test]
+fn first_frame_switches_on_and_settles() {
+    let mut psu = PsuControl::new(CONFIG);
+    assert!(!psu.update(1_000, false));
+    assert_eq!(psu.state(), PsuState::Settling { until_us: SETTLED_US });
+    assert!(psu.supply_on());
+    assert!(psu.pin_level());
+
+    // Frames are held back until the supply has settled, black or not.
+    assert!(!psu.update(1_000 + FRAME_US, false));
+    assert!(!psu.update(1_000 + 2 * FRAME_US, true));
+    assert!(!psu.update(SETTLED_US - 1, false));
+    assert!(psu.update(SETTLED_US, false));
+    assert_eq!(psu.state(), PsuState::On { black_since_us: None });
+}
+
+#[test]
+fn black_output_switches_off_after_the_timeout() {
+    let mut psu = on();
+    let black_us = SETTLED_US + FRAME_US;
+    assert!(psu.update(black_us, true));
+    assert_eq!(psu.state(), PsuState::On { black_since_us: Some(black_us) });
+    assert!(psu.update(black_us + CONFIG.off_after_black_us - 1, true));
+    assert!(!psu.update(black_us + CONFIG.off_after_black_us, true));
+    assert_eq!(psu.state(), PsuState::Off);
+    assert!(!psu.pin_level());
+}
+
+#[test]
+fn color_restarts_the_black_timeout() {
+    let mut psu = on();
+    let black_us = SETTLED_US + FRAME_US;
+    psu.update(black_us, true);
+    let color_us = black_us + CONFIG.off_after_black_us - 1;
+    assert!(psu.update(color_us, false));
+    assert_eq!(psu.state(), PsuState::On { black_since_us: None });
+    assert!(psu.update(black_us + CONFIG.off_after_black_us, true));
+    assert!(psu.update(color_us + CONFIG.off_after_black_us, true));
+    assert!(!psu.update(color_us + 1 + CONFIG.off_after_black_us, true));
+}
+
+#[test]
+fn switching_back_on_settles_again() {
+    let mut psu = on();
+    psu.update(SETTLED_US, true);
+    psu.update(SETTLED_US + CONFIG.off_after_black_us, true);
+    assert_eq!(psu.state(), PsuState::Off);
+
+    let back_on_us = SETTLED_US + 2 * CONFIG.off_after_black_us;
+    assert!(!psu.update(back_on_us, false));
+    assert!(!psu.update(back_on_us + CONFIG.settle_us - 1, false));
+    assert!(psu.update(back_on_us + CONFIG.settle_us, false));
+}
+
+#[test]
+fn active_low_pin() {
+    let mut psu = PsuControl::new(PsuConfig { active_high: false, ..CONFIG });
+    assert!(psu.pin_level());
+    psu.update(0, false);
+    assert!(psu.supply_on());
+    assert!(!psu.pin_level());
+}
@@ -0,0 +1,85 @@
+use esp32c3_smart_led_multi_pin::dither::TemporalDither;
+use esp32c3_smart_led_multi_pin::linear::LinearBuffer;
+use esp32c3_smart_led_multi_pin::output::{Output, OutputMap};
+use esp32c3_smart_led_multi_pin::refresh::{StripRefresh, hash_colors};
+use rgb::{RGB8, RGB16};
+
+const INTERVAL: u32 = 60;
+
+#[test]
+fn first_frame_is_always_sent() {
+    let mut refresh = StripRefresh::new(INTERVAL);
+    assert!(refresh.should_send(0));
+}
+
+#[test]
+fn unchanged_frames_are_skipped() {
+    let mut refresh = StripRefresh::new(INTERVAL);
+    assert!(refresh.should_send(1));
+    assert!(!refresh.should_send(1));
+    assert!(!refresh.should_send(1));
+    assert!(refresh.should_send(2));
+    assert!(!refresh.should_send(2));
+}
+
+#[test]
+fn unchanged_strips_are_resent_every_interval() {
+    let mut refresh = StripRefresh::new(INTERVAL);
+    assert!(refresh.should_send(1));
+    let sent: Vec<u32> = (1..=INTERVAL * 3)
+        .filter(|_| refresh.should_send(1))
+        .collect();
+    assert_eq!(sent, [INTERVAL, INTERVAL * 2, INTERVAL * 3]);
+}
+
+#[test]
+fn a_change_restarts_the_interval() {
+    let mut refresh = StripRefresh::new(INTERVAL);
+    assert!(refresh.should_send(1));
+    for _ in 1..INTERVAL - 1 {
+        assert!(!refresh.should_send(1));
+    }
+    assert!(refresh.should_send(2));
+    for _ in 1..INTERVAL {
+        assert!(!refresh.should_send(2));
+    }
+    assert!(refresh.should_send(2));
+}
+
+#[test]
+fn invalidate_sends_the_next_frame() {
+    let mut refresh = StripRefresh::new(INTERVAL);
+    assert!(refresh.should_send(1));
+    refresh.invalidate();
+    assert!(refresh.should_send(1));
+    assert!(!refresh.should_send(1));
+}
+
+#[test]
+fn hashes_tell_colors_apart() {
+    let hash = |colors: &[RGB8]| hash_colors(colors.iter().copied());
+    let colors = [RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)];
+    assert_eq!(hash(&colors), hash(&colors));
+    assert_ne!(hash(&colors), hash(&[colors[1], colors[0]]));
+    assert_ne!(hash(&colors), hash(&colors[..1]));
+}
+
+#[test]
+fn still_dithered_strips_keep_being_sent() {
+    const OUTPUTS: OutputMap<1> = OutputMap::new([Output { dither: true, ..Output::rgb(0, 4) }]);
+    let mut buffer = LinearBuffer::<4>::new();
+    // Half way between two levels, which dithering alternates between.
+    buffer.colors.fill(RGB16::new(0x0180, 0x0180, 0x0180));
+    let mut dither = TemporalDither::<4>::new();
+    let mut refresh = StripRefresh::new(INTERVAL);
+    let mut sent = 0;
+    for _ in 0..8 {
+        let mut output = [RGB8::default(); 4];
+        buffer.quantize(&OUTPUTS, &mut output, &mut dither);
+        if refresh.should_send(hash_colors(OUTPUTS.outputs[0].colors(&output))) {
+            sent += 1;
+        }
+    }
+    // The frame doesn't change, but skipping it would leave the strip stuck on one level.
+    assert_eq!(sent, 8);
+}
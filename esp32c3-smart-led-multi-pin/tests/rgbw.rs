@@ -0,0 +1,150 @@
+use esp32c3_smart_led_multi_pin::output::{ColorOrder, Output, PixelFormat};
+use esp32c3_smart_led_multi_pin::rgbw::{
+    COOL_WHITE, NEUTRAL_WHITE, RgbwConfig, WARM_WHITE, WhitePoint, WhiteStrategy,
+};
+use rgb::RGB8;
+use smart_leds::{RGBW, White};
+
+// A white LED exactly as cold as the RGB LEDs at full brightness.
+const RGB_WHITE: WhitePoint = RGB8 { r: 255, g: 255, b: 255 };
+
+const fn config(strategy: WhiteStrategy, white_point: WhitePoint) -> RgbwConfig {
+    RgbwConfig { strategy, white_point }
+}
+
+const fn rgbw(r: u8, g: u8, b: u8, white: u8) -> RGBW<u8> {
+    RGBW { r, g, b, a: White(white) }
+}
+
+#[test]
+fn none_leaves_the_white_led_off() {
+    let none = config(WhiteStrategy::None, RGB_WHITE);
+    assert_eq!(
+        none.convert(RGB8::new(200, 200, 200)),
+        rgbw(200, 200, 200, 0)
+    );
+    assert_eq!(none.convert(RGB8::new(10, 20, 30)), rgbw(10, 20, 30, 0));
+}
+
+#[test]
+fn max_moves_greys_onto_a_matching_white_led() {
+    let max = config(WhiteStrategy::Max, RGB_WHITE);
+    assert_eq!(max.convert(RGB8::new(200, 200, 200)), rgbw(0, 0, 0, 200));
+    assert_eq!(max.convert(RGB8::new(10, 20, 30)), rgbw(0, 10, 20, 10));
+}
+
+#[test]
+fn warm_white_only_takes_what_it_can_show() {
+    let max = config(WhiteStrategy::Max, WARM_WHITE);
+    // The warm LED is short on blue and green, which the RGB LEDs make up.
+    assert_eq!(max.convert(RGB8::new(255, 255, 255)), rgbw(0, 75, 148, 255));
+    // Pale blue has more blue than red, so the red channel limits the white.
+    assert_eq!(max.convert(RGB8::new(100, 150, 200)), rgbw(0, 80, 159, 100));
+}
+
+#[test]
+fn saturation_leaves_pure_hues_alone() {
+    let saturation = config(WhiteStrategy::Saturation, NEUTRAL_WHITE);
+    for color in [
+        RGB8::new(255, 0, 0),
+        RGB8::new(0, 255, 0),
+        RGB8::new(0, 0, 255),
+        RGB8::new(255, 255, 0),
+    ] {
+        assert_eq!(
+            saturation.convert(color),
+            rgbw(color.r, color.g, color.b, 0)
+        );
+    }
+    // Greys get all the white `Max` would give them, pale colors some of it.
+    let max = config(WhiteStrategy::Max, NEUTRAL_WHITE);
+    let grey = RGB8::new(180, 180, 180);
+    assert_eq!(saturation.convert(grey), max.convert(grey));
+    let pale = RGB8::new(200, 180, 160);
+    assert!(saturation.convert(pale).a.0 > 0);
+    assert!(saturation.convert(pale).a.0 < max.convert(pale).a.0);
+}
+
+#[test]
+fn conversion_adds_up_to_the_original_color() {
+    let white_points = [RGB_WHITE, WARM_WHITE, NEUTRAL_WHITE, COOL_WHITE];
+    let strategies = [
+        WhiteStrategy::None,
+        WhiteStrategy::Max,
+        WhiteStrategy::Saturation,
+    ];
+    let levels = (0..=255).step_by(15);
+    for white_point in white_points {
+        for strategy in strategies {
+            let config = config(strategy, white_point);
+            for r in levels.clone() {
+                for g in levels.clone() {
+                    for b in levels.clone() {
+                        let color = RGB8::new(r, g, b);
+                        let out = config.convert(color);
+                        // The light the white LED adds to each channel, as `convert` rounds it.
+                        let white = |point: u8| (out.a.0 as u32 * point as u32 / 255) as u8;
+                        assert_eq!(
+                            RGB8::new(
+                                out.r + white(white_point.r),
+                                out.g + white(white_point.g),
+                                out.b + white(white_point.b),
+                            ),
+                            color,
+                            "{strategy:?} {white_point:?} {color:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn white_point_is_reordered_with_the_colors() {
+    let output = |order| Output {
+        format: PixelFormat::Rgbw(config(WhiteStrategy::Max, WARM_WHITE)),
+        order,
+        ..Output::rgb(0, 1)
+    };
+    let config = output(ColorOrder::Grb).rgbw_config().unwrap();
+    assert_eq!(config.white_point, WARM_WHITE);
+    let config = output(ColorOrder::Rgb).rgbw_config().unwrap();
+    assert_eq!(config.white_point, RGB8::new(180, 255, 107));
+    assert_eq!(config.convert(RGB8::new(180, 255, 107)), rgbw(0, 0, 0, 255));
+
+    let rgb = Output { format: PixelFormat::Rgb, ..output(ColorOrder::Grb) };
+    assert_eq!(rgb.rgbw_config(), None);
+}
+
+// The bytes the LED adapter puts on the wire for `colors`, green first.
+fn sent(output: &Output, buffer: &[RGB8]) -> Vec<u8> {
+    output
+        .colors(buffer)
+        .flat_map(|color| [color.g, color.r, color.b])
+        .collect()
+}
+
+#[test]
+fn rgbw_leds_are_packed_into_the_adapter_colors() {
+    let strip = Output {
+        format: PixelFormat::Rgbw(config(WhiteStrategy::None, NEUTRAL_WHITE)),
+        order: ColorOrder::Rgb,
+        ..Output::rgb(0, 2)
+    };
+    assert_eq!(strip.adapter_len(), 3);
+    let buffer = [RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)];
+    // The last color runs past the end of the strip.
+    assert_eq!(sent(&strip, &buffer), [1, 2, 3, 0, 4, 5, 6, 0, 0]);
+
+    let strip = Output {
+        format: PixelFormat::Rgbw(config(WhiteStrategy::Max, WARM_WHITE)),
+        ..Output::rgb(0, 3)
+    };
+    assert_eq!(strip.adapter_len(), 4);
+    let buffer = [WARM_WHITE, RGB8::new(0, 0, 9), RGB8::new(7, 0, 0)];
+    assert_eq!(
+        sent(&strip, &buffer),
+        [0, 0, 0, 255, 0, 0, 9, 0, 0, 7, 0, 0]
+    );
+}
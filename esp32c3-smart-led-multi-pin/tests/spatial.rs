@@ -0,0 +1,116 @@
+use esp32c3_smart_led_multi_pin::layout::{Layout, Wall};
+use esp32c3_smart_led_multi_pin::spatial::{Point, QUARTER_TURN, SpatialMap, WallGeometry};
+
+// A 1m square room on one strip, with LEDs every 100mm. The strip runs along the south and
+// east walls, then the north and west walls from the west end, so those two are reversed to
+// carry on anticlockwise around the room. The west wall is one LED short of the corner.
+const LAYOUT: Layout<4, 1> = Layout::new([
+    Wall { output: 0, len: 10, reverse: false },
+    Wall { output: 0, len: 10, reverse: false },
+    Wall { output: 0, len: 10, reverse: true },
+    Wall { output: 0, len: 9, reverse: true },
+]);
+const NUM_LEDS: usize = LAYOUT.num_leds();
+const NORTH_TRANSLATION: [usize; 10] = LAYOUT.translation_array(2);
+const LOOP_TRANSLATION: [usize; NUM_LEDS] = LAYOUT.loop_translation_array([0, 1, 2, 3]);
+
+const fn wall(start: (i32, i32), end: (i32, i32)) -> WallGeometry {
+    WallGeometry {
+        start: Point::new(start.0, start.1, 0),
+        end: Point::new(end.0, end.1, 0),
+    }
+}
+
+static MAP: SpatialMap<NUM_LEDS> = SpatialMap::new(
+    &LAYOUT,
+    [
+        wall((0, 0), (900, 0)),
+        wall((1000, 0), (1000, 900)),
+        wall((0, 1000), (900, 1000)),
+        wall((0, 100), (0, 900)),
+    ],
+);
+
+const CENTER: Point = Point::new(500, 500, 0);
+
+#[test]
+fn leds_are_spread_evenly_along_their_walls() {
+    assert_eq!(MAP.position(0), Point::new(0, 0, 0));
+    assert_eq!(MAP.position(5), Point::new(500, 0, 0));
+    assert_eq!(MAP.position(19), Point::new(1000, 900, 0));
+    assert_eq!(MAP.position(20), Point::new(0, 1000, 0));
+    assert_eq!(MAP.position(38), Point::new(0, 900, 0));
+    assert_eq!(MAP.distance_mm(5, CENTER), 500);
+}
+
+#[test]
+fn angles_are_measured_anticlockwise_from_x() {
+    assert_eq!(MAP.angle(15, CENTER), 0);
+    assert_eq!(MAP.angle(25, CENTER), QUARTER_TURN);
+    assert_eq!(MAP.angle(34, CENTER), 2 * QUARTER_TURN);
+    assert_eq!(MAP.angle(5, CENTER), 3 * QUARTER_TURN);
+    // The south-west corner is exactly five eighths of a turn round.
+    assert_eq!(MAP.angle(0, CENTER), 2 * QUARTER_TURN + QUARTER_TURN / 2);
+    assert_eq!(MAP.angles(CENTER).count(), NUM_LEDS);
+}
+
+#[test]
+fn angles_wrap_around_at_a_full_turn() {
+    // The LEDs either side of +x are a small step apart, not most of a turn.
+    let below = MAP.angle(14, CENTER);
+    let above = MAP.angle(16, CENTER);
+    assert!(below > 3 * QUARTER_TURN, "{below}");
+    assert!(above < QUARTER_TURN, "{above}");
+    let step = above.wrapping_sub(below);
+    assert!((4_000..4_240).contains(&step), "{step}");
+}
+
+#[test]
+fn nearest_offset_follows_reversed_walls() {
+    // The north wall's animation starts at its east end, its last LED in the logical buffer.
+    assert_eq!(
+        MAP.nearest(Point::new(880, 1010, 0), &NORTH_TRANSLATION),
+        Some(0)
+    );
+    assert_eq!(
+        MAP.nearest_offset(Point::new(880, 1010, 0), &NORTH_TRANSLATION),
+        0
+    );
+    assert_eq!(
+        MAP.nearest_offset(Point::new(20, 1010, 0), &NORTH_TRANSLATION),
+        (9 * u16::MAX as usize / 10) as u16
+    );
+}
+
+#[test]
+fn nearest_offset_wraps_at_the_room_seam() {
+    // The loop starts and ends in the south-west corner, so points either side of it are at
+    // opposite ends of the animation.
+    assert_eq!(
+        MAP.nearest_offset(Point::new(0, -10, 0), &LOOP_TRANSLATION),
+        0
+    );
+    assert_eq!(
+        MAP.nearest_offset(Point::new(-10, 120, 0), &LOOP_TRANSLATION),
+        (38 * u16::MAX as usize / NUM_LEDS) as u16
+    );
+    // The east wall carries on from the south wall, and the north wall from the east wall.
+    assert_eq!(
+        MAP.nearest(Point::new(1000, 0, 0), &LOOP_TRANSLATION),
+        Some(10)
+    );
+    assert_eq!(
+        MAP.nearest(Point::new(1000, 1000, 0), &LOOP_TRANSLATION),
+        Some(19)
+    );
+    assert_eq!(
+        MAP.nearest(Point::new(900, 1010, 0), &LOOP_TRANSLATION),
+        Some(20)
+    );
+}
+
+#[test]
+fn nothing_is_nearest_to_an_empty_list() {
+    assert_eq!(MAP.nearest(CENTER, &[]), None);
+    assert_eq!(MAP.nearest_offset(CENTER, &[]), 0);
+}
@@ -0,0 +1,76 @@
+use esp32c3_smart_led_multi_pin::transition::{
+    Crossfade, Easing, FULL_BLEND, background_offset, blend,
+};
+use rgb::RGB8;
+
+const HALF_BLEND: u16 = FULL_BLEND / 2;
+
+#[test]
+fn easing_starts_and_ends_at_the_ends() {
+    for easing in [Easing::Linear, Easing::EaseInOut] {
+        assert_eq!(easing.apply(0), 0, "{easing:?}");
+        assert_eq!(easing.apply(FULL_BLEND), FULL_BLEND, "{easing:?}");
+    }
+    assert_eq!(Easing::Linear.apply(1234), 1234);
+}
+
+#[test]
+fn ease_in_out_is_slow_at_the_ends_and_even_in_the_middle() {
+    let eased = |progress| Easing::EaseInOut.apply(progress);
+    assert!(eased(FULL_BLEND / 10) < FULL_BLEND / 20);
+    assert!(eased(FULL_BLEND - FULL_BLEND / 10) > FULL_BLEND - FULL_BLEND / 20);
+    assert!(eased(HALF_BLEND).abs_diff(HALF_BLEND) <= 1);
+    let mut last = 0;
+    for progress in (0..=FULL_BLEND).step_by(257) {
+        assert!(eased(progress) >= last, "not increasing at {progress}");
+        last = eased(progress);
+    }
+}
+
+#[test]
+fn crossfade_runs_from_start_to_end() {
+    let fade = Crossfade::new(1_000, 500, Easing::Linear);
+    assert_eq!(fade.amount(0), 0);
+    assert_eq!(fade.amount(1_000), 0);
+    assert_eq!(fade.amount(1_250), HALF_BLEND);
+    assert_eq!(fade.amount(1_500), FULL_BLEND);
+    assert_eq!(fade.amount(9_000), FULL_BLEND);
+    assert!(!fade.is_finished(1_499));
+    assert!(fade.is_finished(1_500));
+}
+
+#[test]
+fn instant_crossfade_is_already_finished() {
+    let fade = Crossfade::new(1_000, 0, Easing::EaseInOut);
+    assert_eq!(fade.amount(1_000), FULL_BLEND);
+    assert!(fade.is_finished(1_000));
+}
+
+#[test]
+fn blend_ends_on_each_buffer() {
+    let from = [RGB8::new(0, 100, 255), RGB8::new(10, 20, 30)];
+    let to = [RGB8::new(255, 100, 0), RGB8::new(30, 20, 10)];
+    let mut output = [RGB8::default(); 2];
+    blend(&from, &to, 0, &mut output);
+    assert_eq!(output, from);
+    blend(&from, &to, FULL_BLEND, &mut output);
+    assert_eq!(output, to);
+    blend(&from, &to, HALF_BLEND, &mut output);
+    assert_eq!(output, [RGB8::new(127, 100, 128), RGB8::new(20, 20, 20)]);
+}
+
+#[test]
+fn background_offset_follows_the_clock() {
+    // A 2s background.
+    let duration_ns = 2_000_000_000;
+    assert_eq!(background_offset(0, duration_ns), 0);
+    assert_eq!(background_offset(1_000_000, duration_ns), HALF_BLEND);
+    assert_eq!(background_offset(1_999_999, duration_ns), u16::MAX - 1);
+    assert_eq!(background_offset(2_000_000, duration_ns), 0);
+    assert_eq!(
+        background_offset(7_500_000, duration_ns),
+        background_offset(1_500_000, duration_ns)
+    );
+    // Less than a microsecond per cycle doesn't divide by zero.
+    assert_eq!(background_offset(12_345, 0), 0);
+}
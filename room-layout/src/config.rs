@@ -0,0 +1,709 @@
+//! The contents of a layout file.
+
+use crate::Error;
+use serde::Deserialize;
+
+/// Number of RMT channels the ESP32-C3 can transmit on, channels 0 and 1. Each one-wire output
+/// needs one of its own, so this is also the most one-wire strips a room can have.
+pub const RMT_TX_CHANNELS: u8 = 2;
+
+/// GPIOs the ESP32-C3 has, 0 to 21.
+pub const GPIO_PINS: u8 = 22;
+
+/// GPIOs that can't drive a strip, and what they are used for instead.
+pub const RESERVED_PINS: [(u8, &str); 11] = [
+    (0, "button 0"),
+    (1, "button 1"),
+    (2, "button 2"),
+    (12, "the flash"),
+    (13, "the flash"),
+    (14, "the flash"),
+    (15, "the flash"),
+    (16, "the flash"),
+    (17, "the flash"),
+    (18, "USB"),
+    (19, "USB"),
+];
+
+/// A whole room.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Room {
+    /// Where triggers fired at a point start from. Defaults to the origin.
+    #[serde(default)]
+    pub trigger_origin: Point,
+    /// Frames per second the firmware renders at. Every strip has to be sent within one frame.
+    #[serde(default = "default_frame_rate")]
+    pub frame_rate_hz: u32,
+    /// Animation settings for every wall that doesn't set its own.
+    #[serde(default)]
+    pub animation: AnimationSettings,
+    /// Physical strips, in the order their LEDs appear in the logical buffer.
+    #[serde(rename = "output")]
+    pub outputs: Vec<OutputConfig>,
+    /// Walls, in the order their LEDs appear in the logical buffer.
+    #[serde(rename = "wall")]
+    pub walls: Vec<WallConfig>,
+    /// Animating the whole room as one loop.
+    #[serde(rename = "loop", default)]
+    pub room_loop: LoopConfig,
+    /// Power supplies and the strips they feed.
+    #[serde(rename = "supply")]
+    pub supplies: Vec<SupplyConfig>,
+    /// Current draw of the LEDs, for estimating each supply's load.
+    #[serde(default)]
+    pub power: PowerSettings,
+    #[serde(default)]
+    pub psu: PsuSettings,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    /// RMT channel the strip is driven from, 0 or 1. `apa102` strips are driven from SPI instead
+    /// and don't have one.
+    pub rmt_channel: Option<u8>,
+    /// GPIO the strip's data line is attached to.
+    pub pin: u8,
+    /// GPIO the clock line of an `apa102` strip is attached to.
+    pub clock_pin: Option<u8>,
+    #[serde(default)]
+    pub reverse: bool,
+    /// Temporal dithering for smoother dim fades. Off by default.
+    #[serde(default)]
+    pub dither: bool,
+    #[serde(default)]
+    pub format: Format,
+    /// Only used by `rgbw` outputs. Defaults to `saturation`.
+    pub white_strategy: Option<WhiteStrategy>,
+    /// Only used by `rgbw` outputs. Defaults to `neutral`.
+    pub white_point: Option<WhitePoint>,
+    /// Channel order of one-wire strips. Defaults to `grb`.
+    pub order: Option<ColorOrder>,
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+    /// Scales every color sent to the strip, out of 255. Baked into the output's gamma table.
+    #[serde(default = "default_brightness")]
+    pub brightness: u8,
+    /// Positions of LEDs that have been cut out or bypassed, counted along the strip as it was
+    /// installed from 0 at the end the data comes in. Nothing is sent for these positions, so
+    /// the LEDs after them stay lined up with their walls.
+    #[serde(default)]
+    pub missing: Vec<usize>,
+    /// Positions of LEDs that are always sent black, counted the same way as `missing`.
+    #[serde(default)]
+    pub force_black: Vec<usize>,
+}
+
+impl OutputConfig {
+    /// Every GPIO the strip is attached to.
+    pub fn pins(&self) -> impl Iterator<Item = u8> {
+        [Some(self.pin), self.clock_pin].into_iter().flatten()
+    }
+}
+
+/// What one LED on a strip shows, as worked out by `Room::physical_leds`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicalLed {
+    /// The color at this index of the logical buffer.
+    Logical(usize),
+    /// Always black.
+    Black,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// One-wire strips such as the WS2812.
+    #[default]
+    Rgb,
+    /// One-wire strips with a white LED, such as the SK6812 RGBW.
+    Rgbw,
+    /// Clocked APA102 / SK9822 strips, driven from SPI.
+    Apa102,
+}
+
+impl Format {
+    /// Time to send one LED, rounded up. One-wire strips run at the WS2812's 800kHz bit rate and
+    /// APA102s at the firmware's 4MHz SPI clock.
+    pub fn send_us_per_led(self) -> usize {
+        match self {
+            Format::Rgb => 30,
+            Format::Rgbw => 40,
+            Format::Apa102 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhiteStrategy {
+    None,
+    Max,
+    #[default]
+    Saturation,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhitePoint {
+    Warm,
+    #[default]
+    Neutral,
+    Cool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    #[default]
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WallConfig {
+    /// Lowercase name, used for the wall's constants (`closet` gives `CLOSET_WALL`,
+    /// `NUM_LEDS_CLOSET_WALL`, `CLOSET_TRANSLATION`, ...).
+    pub name: String,
+    /// Index of the strip the wall is on.
+    pub output: usize,
+    pub len: usize,
+    #[serde(default)]
+    pub reverse: bool,
+    /// Position of the wall's first LED in the logical buffer.
+    pub start: Point,
+    /// Position of the wall's last LED in the logical buffer.
+    pub end: Point,
+    #[serde(default)]
+    pub correction: Correction,
+    /// Overrides for the room's animation settings.
+    #[serde(default)]
+    pub animation: AnimationSettings,
+}
+
+/// A position in the room in millimetres, written `[x, y]` or `[x, y, z]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Vec<i32>")]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl TryFrom<Vec<i32>> for Point {
+    type Error = &'static str;
+
+    fn try_from(coordinates: Vec<i32>) -> Result<Self, Self::Error> {
+        match coordinates[..] {
+            [x, y] => Ok(Point { x, y, z: 0 }),
+            [x, y, z] => Ok(Point { x, y, z }),
+            _ => Err("positions are written [x, y] or [x, y, z], in millimetres"),
+        }
+    }
+}
+
+/// Brightness and white balance scale factors out of 255.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Correction {
+    pub brightness: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Correction { brightness: 255, r: 255, g: 255, b: 255 }
+    }
+}
+
+/// Animation settings. Anything left out falls back to the room's settings, then to the
+/// defaults in `ResolvedAnimation`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationSettings {
+    /// Index into the list of background palettes in `main.rs`.
+    pub bg_rainbow: Option<usize>,
+    pub bg_duration_ms: Option<u64>,
+    pub bg_subdivisions: Option<usize>,
+    pub trig_duration_ms: Option<u64>,
+}
+
+/// Animation settings with every fallback applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolvedAnimation {
+    pub bg_rainbow: usize,
+    pub bg_duration_ms: u64,
+    pub bg_subdivisions: usize,
+    pub trig_duration_ms: u64,
+}
+
+impl AnimationSettings {
+    /// Fills in anything not set here from `fallback`.
+    pub fn or(&self, fallback: &AnimationSettings) -> AnimationSettings {
+        AnimationSettings {
+            bg_rainbow: self.bg_rainbow.or(fallback.bg_rainbow),
+            bg_duration_ms: self.bg_duration_ms.or(fallback.bg_duration_ms),
+            bg_subdivisions: self.bg_subdivisions.or(fallback.bg_subdivisions),
+            trig_duration_ms: self.trig_duration_ms.or(fallback.trig_duration_ms),
+        }
+    }
+
+    pub fn resolve(&self) -> ResolvedAnimation {
+        ResolvedAnimation {
+            bg_rainbow: self.bg_rainbow.unwrap_or(0),
+            bg_duration_ms: self.bg_duration_ms.unwrap_or(20_000),
+            bg_subdivisions: self.bg_subdivisions.unwrap_or(1),
+            trig_duration_ms: self.trig_duration_ms.unwrap_or(5_000),
+        }
+    }
+}
+
+/// One animation running around the whole room.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoopConfig {
+    /// Names of every wall, in order around the room. Each wall is run in the same direction as
+    /// its own animation, so `reverse` has to be set to suit. Defaults to the order of the walls
+    /// in the file.
+    pub walls: Option<Vec<String>>,
+    /// Overrides for the room's animation settings.
+    #[serde(default)]
+    pub animation: AnimationSettings,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SupplyConfig {
+    /// Indices of the strips the supply feeds.
+    pub outputs: Vec<usize>,
+    pub budget_ma: u32,
+}
+
+/// Current draw of one LED package, in microamps. Defaults to typical WS2812B figures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct PowerSettings {
+    /// Current through one color channel at full duty.
+    pub channel_full_ua: u32,
+    /// Current drawn by the LED's controller while showing black.
+    pub idle_ua: u32,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        PowerSettings { channel_full_ua: 20_000, idle_ua: 1_000 }
+    }
+}
+
+/// LED supply switching, see the example's `psu` module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct PsuSettings {
+    /// GPIO driving the relay or MOSFET. Defaults to 7.
+    pub pin: u8,
+    pub off_after_black_ms: u64,
+    pub settle_ms: u64,
+    pub active_high: bool,
+}
+
+impl Default for PsuSettings {
+    fn default() -> Self {
+        PsuSettings {
+            pin: 7,
+            off_after_black_ms: 60_000,
+            settle_ms: 250,
+            active_high: true,
+        }
+    }
+}
+
+fn default_frame_rate() -> u32 {
+    60
+}
+
+fn default_gamma() -> f32 {
+    2.8
+}
+
+fn default_brightness() -> u8 {
+    255
+}
+
+impl Room {
+    /// The animation settings for wall `wall`, with the room's settings and the defaults filled
+    /// in.
+    pub fn wall_animation(&self, wall: usize) -> ResolvedAnimation {
+        self.walls[wall].animation.or(&self.animation).resolve()
+    }
+
+    /// The animation settings for the whole room loop, with the room's settings and the defaults
+    /// filled in.
+    pub fn loop_animation(&self) -> ResolvedAnimation {
+        self.room_loop.animation.or(&self.animation).resolve()
+    }
+
+    /// Indices of the walls in order around the room loop.
+    pub fn loop_walls(&self) -> Vec<usize> {
+        match &self.room_loop.walls {
+            Some(names) => names
+                .iter()
+                .filter_map(|name| self.walls.iter().position(|wall| &wall.name == name))
+                .collect(),
+            None => (0..self.walls.len()).collect(),
+        }
+    }
+
+    /// Total number of LEDs in the room.
+    pub fn num_leds(&self) -> usize {
+        self.walls.iter().map(|wall| wall.len).sum()
+    }
+
+    /// Index in the logical buffer of the first LED on wall `wall`.
+    pub fn start_index(&self, wall: usize) -> usize {
+        self.walls[..wall].iter().map(|wall| wall.len).sum()
+    }
+
+    /// Index in the logical buffer of the first LED on output `output`.
+    pub fn output_start(&self, output: usize) -> usize {
+        self.walls
+            .iter()
+            .take_while(|wall| wall.output < output)
+            .map(|wall| wall.len)
+            .sum()
+    }
+
+    /// Number of LED positions on output `output`, including any missing ones.
+    pub fn output_len(&self, output: usize) -> usize {
+        self.walls
+            .iter()
+            .filter(|wall| wall.output == output)
+            .map(|wall| wall.len)
+            .sum()
+    }
+
+    /// Whether output `output` has missing or forced black LEDs, and so needs its LEDs listed.
+    pub fn has_masked_leds(&self, output: usize) -> bool {
+        let output = &self.outputs[output];
+        !output.missing.is_empty() || !output.force_black.is_empty()
+    }
+
+    /// What each LED actually on output `output` shows, in the order they are sent.
+    pub fn physical_leds(&self, output: usize) -> Vec<PhysicalLed> {
+        let start = self.output_start(output);
+        let len = self.output_len(output);
+        let config = &self.outputs[output];
+        (0..len)
+            .filter(|position| !config.missing.contains(position))
+            .map(|position| {
+                if config.force_black.contains(&position) {
+                    PhysicalLed::Black
+                } else if config.reverse {
+                    PhysicalLed::Logical(start + len - 1 - position)
+                } else {
+                    PhysicalLed::Logical(start + position)
+                }
+            })
+            .collect()
+    }
+
+    /// Checks everything the firmware would otherwise only catch at compile time or not at all,
+    /// and explains what is wrong in terms of the layout file.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.outputs.is_empty() {
+            return Err(Error::layout("the room has no outputs"));
+        }
+        let psu_pin = self.psu.pin;
+        if psu_pin >= GPIO_PINS {
+            return Err(Error::layout(format!(
+                "the psu uses GPIO {psu_pin}, but the ESP32-C3 has no such pin"
+            )));
+        }
+        if let Some((_, used_by)) = RESERVED_PINS.iter().find(|(p, _)| *p == psu_pin) {
+            return Err(Error::layout(format!(
+                "the psu uses GPIO {psu_pin}, which is used by {used_by}"
+            )));
+        }
+        for (i, output) in self.outputs.iter().enumerate() {
+            let apa102 = output.format == Format::Apa102;
+            match output.rmt_channel {
+                Some(_) if apa102 => {
+                    return Err(Error::output(
+                        i,
+                        "is an apa102 strip, which is driven from SPI rather than an RMT channel",
+                    ));
+                }
+                None if !apa102 => return Err(Error::output(i, "needs an rmt_channel")),
+                Some(channel) if channel >= RMT_TX_CHANNELS => {
+                    return Err(Error::output(
+                        i,
+                        format!(
+                            "uses RMT channel {channel}, but the ESP32-C3 can only transmit on \
+                             channels 0 and 1"
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+            match output.clock_pin {
+                None if apa102 => {
+                    return Err(Error::output(
+                        i,
+                        "is an apa102 strip, which needs a clock_pin",
+                    ));
+                }
+                Some(_) if !apa102 => {
+                    return Err(Error::output(i, "clock_pin only applies to apa102 outputs"));
+                }
+                _ => {}
+            }
+            if apa102 && output.order.is_some() {
+                return Err(Error::output(
+                    i,
+                    "order only applies to one-wire strips, apa102 strips always take blue, \
+                     green, red",
+                ));
+            }
+            if apa102 && self.outputs[..i].iter().any(|o| o.format == Format::Apa102) {
+                return Err(Error::output(
+                    i,
+                    "is a second apa102 strip, but the ESP32-C3 only has one SPI peripheral to \
+                     drive them from",
+                ));
+            }
+            for pin in output.pins() {
+                if pin >= GPIO_PINS {
+                    return Err(Error::output(
+                        i,
+                        format!("uses GPIO {pin}, but the ESP32-C3 has no such pin"),
+                    ));
+                }
+                if let Some((_, used_by)) = RESERVED_PINS.iter().find(|(p, _)| *p == pin) {
+                    return Err(Error::output(
+                        i,
+                        format!("uses GPIO {pin}, which is used by {used_by}"),
+                    ));
+                }
+                if pin == psu_pin {
+                    return Err(Error::output(
+                        i,
+                        format!("uses GPIO {pin}, which is used by the LED supply switch"),
+                    ));
+                }
+            }
+            if output.rmt_channel.is_some()
+                && self.outputs[..i]
+                    .iter()
+                    .any(|o| o.rmt_channel == output.rmt_channel)
+            {
+                return Err(Error::output(
+                    i,
+                    "uses the same RMT channel as an earlier output",
+                ));
+            }
+            if output.clock_pin == Some(output.pin) {
+                return Err(Error::output(i, "uses the same pin for data and clock"));
+            }
+            if self.outputs[..i]
+                .iter()
+                .flat_map(OutputConfig::pins)
+                .any(|pin| output.pins().any(|p| p == pin))
+            {
+                return Err(Error::output(i, "uses the same pin as an earlier output"));
+            }
+            if !(output.gamma.is_finite() && output.gamma > 0.0) {
+                return Err(Error::output(i, "gamma has to be a positive number"));
+            }
+            let white_settings = output.white_strategy.is_some() || output.white_point.is_some();
+            if output.format != Format::Rgbw && white_settings {
+                return Err(Error::output(
+                    i,
+                    "white_strategy and white_point only apply to rgbw outputs",
+                ));
+            }
+        }
+
+        if self.walls.is_empty() {
+            return Err(Error::layout("the room has no walls"));
+        }
+        // Number of outputs that have had a wall so far.
+        let mut covered = 0;
+        for (i, wall) in self.walls.iter().enumerate() {
+            if !is_wall_name(&wall.name) {
+                return Err(Error::wall(
+                    i,
+                    &wall.name,
+                    "name has to start with a lowercase letter and only use lowercase letters, \
+                     digits and underscores",
+                ));
+            }
+            if self.walls[..i].iter().any(|w| w.name == wall.name) {
+                return Err(Error::wall(
+                    i,
+                    &wall.name,
+                    "name is already used by an earlier wall",
+                ));
+            }
+            if wall.len == 0 {
+                return Err(Error::wall(i, &wall.name, "has no LEDs"));
+            }
+            if wall.output >= self.outputs.len() {
+                return Err(Error::wall(
+                    i,
+                    &wall.name,
+                    format!(
+                        "is on output {}, but the room only has {} outputs",
+                        wall.output,
+                        self.outputs.len()
+                    ),
+                ));
+            }
+            if wall.output + 1 < covered {
+                return Err(Error::wall(
+                    i,
+                    &wall.name,
+                    format!(
+                        "is on output {} after a wall on output {}, walls have to be listed in \
+                         output order",
+                        wall.output,
+                        covered - 1
+                    ),
+                ));
+            }
+            if wall.output > covered {
+                return Err(Error::output(covered, "has no walls"));
+            }
+            covered = wall.output + 1;
+        }
+        if covered < self.outputs.len() {
+            return Err(Error::output(covered, "has no walls"));
+        }
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            let len = self.output_len(i);
+            let lists = [
+                ("missing", &output.missing),
+                ("force_black", &output.force_black),
+            ];
+            for (list, positions) in lists {
+                for (j, &position) in positions.iter().enumerate() {
+                    if position >= len {
+                        return Err(Error::output(
+                            i,
+                            format!("{list} position {position} is past the end of its {len} LEDs"),
+                        ));
+                    }
+                    if positions[..j].contains(&position) {
+                        return Err(Error::output(
+                            i,
+                            format!("{list} lists position {position} more than once"),
+                        ));
+                    }
+                }
+            }
+            if let Some(position) = output
+                .force_black
+                .iter()
+                .find(|position| output.missing.contains(position))
+            {
+                return Err(Error::output(
+                    i,
+                    format!("position {position} is both missing and forced black"),
+                ));
+            }
+            if output.missing.len() == len {
+                return Err(Error::output(i, "has every LED missing"));
+            }
+        }
+
+        if self.frame_rate_hz == 0 {
+            return Err(Error::layout("frame_rate_hz has to be at least 1"));
+        }
+        let frame_us = 1_000_000 / self.frame_rate_hz as usize;
+        for i in 0..self.outputs.len() {
+            let send_us = self.physical_leds(i).len() * self.outputs[i].format.send_us_per_led();
+            if send_us > frame_us {
+                return Err(Error::output(
+                    i,
+                    format!(
+                        "takes {send_us}us to send, longer than a frame at {} Hz",
+                        self.frame_rate_hz
+                    ),
+                ));
+            }
+        }
+
+        if let Some(names) = &self.room_loop.walls {
+            for (i, name) in names.iter().enumerate() {
+                if !self.walls.iter().any(|wall| &wall.name == name) {
+                    return Err(Error::layout(format!(
+                        "the loop has no wall named `{name}`"
+                    )));
+                }
+                if names[..i].contains(name) {
+                    return Err(Error::layout(format!(
+                        "the loop goes through wall `{name}` more than once"
+                    )));
+                }
+            }
+            if let Some(wall) = self.walls.iter().find(|wall| !names.contains(&wall.name)) {
+                return Err(Error::layout(format!(
+                    "the loop doesn't go through wall `{}`",
+                    wall.name
+                )));
+            }
+        }
+
+        for (i, supply) in self.supplies.iter().enumerate() {
+            if supply.outputs.is_empty() {
+                return Err(Error::supply(i, "feeds no outputs"));
+            }
+            for &output in &supply.outputs {
+                if output >= self.outputs.len() {
+                    return Err(Error::supply(
+                        i,
+                        format!(
+                            "feeds output {output}, but the room only has {} outputs",
+                            self.outputs.len()
+                        ),
+                    ));
+                }
+                let listed = self.supplies[..=i]
+                    .iter()
+                    .flat_map(|supply| &supply.outputs)
+                    .filter(|&&o| o == output);
+                if listed.count() > 1 {
+                    return Err(Error::output(output, "is listed on more than one supply"));
+                }
+            }
+        }
+        for i in 0..self.outputs.len() {
+            if !self
+                .supplies
+                .iter()
+                .any(|supply| supply.outputs.contains(&i))
+            {
+                return Err(Error::output(i, "isn't fed by any supply"));
+            }
+        }
+        if self.power.channel_full_ua == 0 {
+            return Err(Error::layout(
+                "power.channel_full_ua has to be more than 0, or no frame is ever over budget",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn is_wall_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
@@ -0,0 +1,40 @@
+use core::fmt;
+
+/// Why a layout file couldn't be used.
+#[derive(Debug)]
+pub enum Error {
+    /// The file isn't valid TOML, or doesn't match the layout format. The message includes the
+    /// line and column of the problem.
+    Toml(toml::de::Error),
+    /// The file parsed but describes a room the firmware can't drive.
+    Layout(String),
+}
+
+impl Error {
+    pub(crate) fn layout(message: impl Into<String>) -> Self {
+        Error::Layout(message.into())
+    }
+
+    pub(crate) fn output(index: usize, message: impl fmt::Display) -> Self {
+        Error::Layout(format!("output {index} {message}"))
+    }
+
+    pub(crate) fn wall(index: usize, name: &str, message: impl fmt::Display) -> Self {
+        Error::Layout(format!("wall {index} (`{name}`) {message}"))
+    }
+
+    pub(crate) fn supply(index: usize, message: impl fmt::Display) -> Self {
+        Error::Layout(format!("supply {index} {message}"))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Toml(error) => write!(f, "{error}"),
+            Error::Layout(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,842 @@
+//! Rust source for the example's `room` module.
+
+use crate::config::{
+    ColorOrder, Format, PhysicalLed, Point, ResolvedAnimation, Room, WhitePoint, WhiteStrategy,
+};
+use core::fmt::Write;
+
+const ANIMATIONS: &str = "::lighting_controller::animations";
+
+/// Generates the contents of the `room` module for `room`: the same constants a hand-written
+/// layout module would have, plus a `room_animations!` macro that sets up one animation per wall,
+/// a `room_loop_animation!` macro that sets up one around the whole room, the `room_outputs!`,
+/// `room_write_outputs!` and `room_reinit_outputs!` macros that drive each strip from the RMT
+/// channel or SPI peripheral and pins the layout gives it, and a `room_psu_pin!` macro naming the
+/// pin switching the LED supplies. `source` is the path of the layout file, for the header
+/// comment.
+pub fn generate(room: &Room, source: &str) -> String {
+    let mut out = String::new();
+    write_layout(&mut out, room, source).expect("Writing to a String can't fail.");
+    write_animations(&mut out, room).expect("Writing to a String can't fail.");
+    write_outputs(&mut out, room).expect("Writing to a String can't fail.");
+    write_psu_pin(&mut out, room).expect("Writing to a String can't fail.");
+    out
+}
+
+fn write_layout(out: &mut String, room: &Room, source: &str) -> core::fmt::Result {
+    let walls = room.walls.len();
+    let outputs = room.outputs.len();
+    let names: Vec<String> = room
+        .walls
+        .iter()
+        .map(|wall| wall.name.to_uppercase())
+        .collect();
+
+    writeln!(
+        out,
+        "// Generated by build.rs from {source}, edit that file instead."
+    )?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "use crate::correction::{{ColorCorrection, CorrectionZone}};"
+    )?;
+    writeln!(out, "use crate::gamma::GammaLut;")?;
+    writeln!(out, "use crate::layout::{{Layout, Wall}};")?;
+    // `PhysicalLed` is only needed for outputs with missing or forced black LEDs.
+    let physical_led = if (0..outputs).any(|i| room.has_masked_leds(i)) {
+        "PhysicalLed, "
+    } else {
+        ""
+    };
+    writeln!(
+        out,
+        "use crate::output::{{ColorOrder, Driver, Output, OutputMap, {physical_led}PixelFormat}};"
+    )?;
+    writeln!(
+        out,
+        "use crate::power::{{PowerLimiter, PowerModel, Supply}};"
+    )?;
+    writeln!(out, "use crate::psu::PsuConfig;")?;
+    writeln!(
+        out,
+        "use crate::spatial::{{Point, SpatialMap, WallGeometry}};"
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "// wall indices into LAYOUT")?;
+    for (i, name) in names.iter().enumerate() {
+        writeln!(out, "pub const {name}_WALL: usize = {i};")?;
+    }
+    writeln!(out, "pub const NUM_WALLS: usize = {walls};")?;
+    let quoted: Vec<String> = room
+        .walls
+        .iter()
+        .map(|wall| format!("{:?}", wall.name))
+        .collect();
+    writeln!(
+        out,
+        "pub const WALL_NAMES: [&str; NUM_WALLS] = [{}];",
+        quoted.join(", ")
+    )?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "// every wall in the order its LEDs appear in the logical array, with the strip it is \
+         wired to"
+    )?;
+    writeln!(
+        out,
+        "pub const LAYOUT: Layout<{walls}, {outputs}> = Layout::new(["
+    )?;
+    for wall in &room.walls {
+        writeln!(out, "    // {} wall", wall.name)?;
+        writeln!(
+            out,
+            "    Wall {{ output: {}, len: {}, reverse: {} }},",
+            wall.output, wall.len, wall.reverse
+        )?;
+    }
+    writeln!(out, "]);")?;
+    writeln!(out)?;
+
+    writeln!(out, "// index for LED strip in logical array")?;
+    for name in &names {
+        writeln!(
+            out,
+            "pub const START_{name}_INDEX: usize = LAYOUT.start_index({name}_WALL);"
+        )?;
+    }
+    writeln!(out)?;
+    for name in &names {
+        writeln!(
+            out,
+            "pub const NUM_LEDS_{name}_WALL: usize = LAYOUT.wall_len({name}_WALL);"
+        )?;
+    }
+    writeln!(out)?;
+
+    writeln!(out, "// translation arrays for each wall's animation")?;
+    for name in &names {
+        writeln!(
+            out,
+            "pub const {name}_TRANSLATION: [usize; NUM_LEDS_{name}_WALL] =\n    \
+             LAYOUT.translation_array({name}_WALL);"
+        )?;
+    }
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "// walls in order around the room, for animations running around the whole room"
+    )?;
+    let loop_walls: Vec<String> = room
+        .loop_walls()
+        .into_iter()
+        .map(|wall| format!("{}_WALL", names[wall]))
+        .collect();
+    writeln!(
+        out,
+        "pub const ROOM_LOOP: [usize; NUM_WALLS] = [{}];",
+        loop_walls.join(", ")
+    )?;
+    writeln!(
+        out,
+        "pub const LOOP_TRANSLATION: [usize; NUM_LEDS] = LAYOUT.loop_translation_array(ROOM_LOOP);"
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "// translation array of each wall, by wall index")?;
+    let translations: Vec<String> = names
+        .iter()
+        .map(|name| format!("&{name}_TRANSLATION"))
+        .collect();
+    writeln!(
+        out,
+        "pub const TRANSLATIONS: [&[usize]; NUM_WALLS] = [{}];",
+        translations.join(", ")
+    )?;
+    writeln!(out)?;
+
+    for i in (0..outputs).filter(|&i| room.has_masked_leds(i)) {
+        let leds: Vec<String> = room
+            .physical_leds(i)
+            .into_iter()
+            .map(|led| match led {
+                PhysicalLed::Logical(index) => format!("PhysicalLed::Logical({index})"),
+                PhysicalLed::Black => "PhysicalLed::Black".to_string(),
+            })
+            .collect();
+        writeln!(
+            out,
+            "// what each LED on output {i} shows, in wire order without the missing ones"
+        )?;
+        writeln!(
+            out,
+            "const OUTPUT_{i}_LEDS: [PhysicalLed; {}] = [",
+            leds.len()
+        )?;
+        for line in leds.chunks(3) {
+            writeln!(out, "    {},", line.join(", "))?;
+        }
+        writeln!(out, "];")?;
+        writeln!(out)?;
+    }
+
+    writeln!(
+        out,
+        "// physical strips, in the same order as the LED adapters are created in main"
+    )?;
+    writeln!(
+        out,
+        "pub const OUTPUTS: OutputMap<{outputs}> = OutputMap::new(["
+    )?;
+    for (i, output) in room.outputs.iter().enumerate() {
+        let on_output: Vec<&str> = room
+            .walls
+            .iter()
+            .filter(|wall| wall.output == i)
+            .map(|wall| wall.name.as_str())
+            .collect();
+        writeln!(out, "    // {}", wall_list(&on_output))?;
+        writeln!(out, "    Output {{")?;
+        match (output.rmt_channel, output.clock_pin) {
+            (Some(channel), _) => writeln!(
+                out,
+                "        driver: Driver::Rmt {{ channel: {channel}, pin: {} }},",
+                output.pin
+            )?,
+            (None, Some(clock_pin)) => writeln!(
+                out,
+                "        driver: Driver::Spi {{ data_pin: {}, clock_pin: {clock_pin} }},",
+                output.pin
+            )?,
+            (None, None) => unreachable!("Checked by `Room::validate`."),
+        }
+        writeln!(out, "        start: LAYOUT.output_start({i}),")?;
+        writeln!(out, "        len: LAYOUT.output_len({i}),")?;
+        writeln!(out, "        reverse: {},", output.reverse)?;
+        writeln!(out, "        dither: {},", output.dither)?;
+        match output.format {
+            Format::Rgb | Format::Apa102 => writeln!(out, "        format: PixelFormat::Rgb,")?,
+            Format::Rgbw => {
+                let strategy = match output.white_strategy.unwrap_or_default() {
+                    WhiteStrategy::None => "None",
+                    WhiteStrategy::Max => "Max",
+                    WhiteStrategy::Saturation => "Saturation",
+                };
+                let white_point = match output.white_point.unwrap_or_default() {
+                    WhitePoint::Warm => "WARM_WHITE",
+                    WhitePoint::Neutral => "NEUTRAL_WHITE",
+                    WhitePoint::Cool => "COOL_WHITE",
+                };
+                writeln!(
+                    out,
+                    "        format: PixelFormat::Rgbw(crate::rgbw::RgbwConfig {{"
+                )?;
+                writeln!(
+                    out,
+                    "            strategy: crate::rgbw::WhiteStrategy::{strategy},"
+                )?;
+                writeln!(out, "            white_point: crate::rgbw::{white_point},")?;
+                writeln!(out, "        }}),")?;
+            }
+        }
+        let order = match output.order.unwrap_or_default() {
+            ColorOrder::Rgb => "Rgb",
+            ColorOrder::Rbg => "Rbg",
+            ColorOrder::Grb => "Grb",
+            ColorOrder::Gbr => "Gbr",
+            ColorOrder::Brg => "Brg",
+            ColorOrder::Bgr => "Bgr",
+        };
+        writeln!(out, "        order: ColorOrder::{order},")?;
+        if room.has_masked_leds(i) {
+            writeln!(out, "        leds: Some(&OUTPUT_{i}_LEDS),")?;
+        } else {
+            writeln!(out, "        leds: None,")?;
+        }
+        writeln!(out, "    }},")?;
+    }
+    writeln!(out, "]);")?;
+    writeln!(out, "pub const NUM_OUTPUTS: usize = {outputs};")?;
+    writeln!(out, "pub const NUM_LEDS: usize = OUTPUTS.total_len();")?;
+    writeln!(out)?;
+
+    writeln!(out, "// gamma curve and brightness for each output")?;
+    let gammas: Vec<String> = room
+        .outputs
+        .iter()
+        .map(|output| {
+            format!(
+                "GammaLut::with_brightness({:?}, {})",
+                output.gamma, output.brightness
+            )
+        })
+        .collect();
+    writeln!(
+        out,
+        "pub const OUTPUT_GAMMA: [GammaLut; {outputs}] = [{}];",
+        gammas.join(", ")
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "// per-wall brightness and white balance")?;
+    writeln!(
+        out,
+        "pub const WALL_CORRECTIONS: [CorrectionZone; {walls}] = LAYOUT.correction_zones(["
+    )?;
+    for wall in &room.walls {
+        let c = wall.correction;
+        writeln!(out, "    // {} wall", wall.name)?;
+        writeln!(
+            out,
+            "    ColorCorrection {{ brightness: {}, r: {}, g: {}, b: {} }},",
+            c.brightness, c.r, c.g, c.b
+        )?;
+    }
+    writeln!(out, "]);")?;
+    writeln!(out)?;
+
+    writeln!(out, "// supplies and the strips they feed")?;
+    writeln!(
+        out,
+        "pub const POWER_LIMITER: PowerLimiter<{}> = PowerLimiter::new(",
+        room.supplies.len()
+    )?;
+    writeln!(out, "    PowerModel {{")?;
+    writeln!(
+        out,
+        "        channel_full_ua: {},",
+        grouped(room.power.channel_full_ua as u64)
+    )?;
+    writeln!(
+        out,
+        "        idle_ua: {},",
+        grouped(room.power.idle_ua as u64)
+    )?;
+    writeln!(out, "    }},")?;
+    writeln!(out, "    [")?;
+    for supply in &room.supplies {
+        let outputs: Vec<String> = supply.outputs.iter().map(usize::to_string).collect();
+        writeln!(
+            out,
+            "        Supply {{ outputs: &[{}], budget_ma: {} }},",
+            outputs.join(", "),
+            grouped(supply.budget_ma as u64)
+        )?;
+    }
+    writeln!(out, "    ],")?;
+    writeln!(out, ");")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "// where each wall's first and last LED in the logical array are, in millimetres"
+    )?;
+    writeln!(out, "pub const WALL_GEOMETRY: [WallGeometry; {walls}] = [")?;
+    for wall in &room.walls {
+        writeln!(out, "    // {} wall", wall.name)?;
+        writeln!(out, "    WallGeometry {{")?;
+        writeln!(out, "        start: {},", point(wall.start))?;
+        writeln!(out, "        end: {},", point(wall.end))?;
+        writeln!(out, "    }},")?;
+    }
+    writeln!(out, "];")?;
+    writeln!(out)?;
+
+    writeln!(out, "// position of every LED in the room")?;
+    writeln!(
+        out,
+        "pub static SPATIAL_MAP: SpatialMap<NUM_LEDS> = SpatialMap::new(&LAYOUT, WALL_GEOMETRY);"
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "// where triggers fired at a point start from")?;
+    writeln!(
+        out,
+        "pub const TRIGGER_ORIGIN: Point = {};",
+        point(room.trigger_origin)
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "// frames per second the animations are rendered at")?;
+    writeln!(
+        out,
+        "pub const FRAME_RATE_HZ: u32 = {};",
+        room.frame_rate_hz
+    )?;
+    writeln!(out)?;
+
+    writeln!(out, "// LED supply switching")?;
+    writeln!(out, "pub const PSU_CONTROL: PsuConfig = PsuConfig {{")?;
+    writeln!(
+        out,
+        "    off_after_black_us: {},",
+        grouped(room.psu.off_after_black_ms * 1_000)
+    )?;
+    writeln!(
+        out,
+        "    settle_us: {},",
+        grouped(room.psu.settle_ms * 1_000)
+    )?;
+    writeln!(out, "    active_high: {},", room.psu.active_high)?;
+    writeln!(out, "}};")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "// palette the room starts on, the next palette button moves on from here"
+    )?;
+    writeln!(
+        out,
+        "pub const BG_RAINBOW: usize = {};",
+        room.animation.resolve().bg_rainbow
+    )?;
+    writeln!(out)?;
+
+    let durations: Vec<String> = (0..room.walls.len())
+        .map(|i| grouped(room.wall_animation(i).bg_duration_ms * 1_000_000))
+        .collect();
+    writeln!(
+        out,
+        "// background cycle times of each wall's animation and of the loop's, to keep the two"
+    )?;
+    writeln!(out, "// copies of each background in step")?;
+    writeln!(
+        out,
+        "pub const WALL_BG_DURATIONS_NS: [u64; NUM_WALLS] = [{}];",
+        durations.join(", ")
+    )?;
+    writeln!(
+        out,
+        "pub const LOOP_BG_DURATION_NS: u64 = {};",
+        grouped(room.loop_animation().bg_duration_ms * 1_000_000)
+    )?;
+    writeln!(out)
+}
+
+fn write_animations(out: &mut String, room: &Room) -> core::fmt::Result {
+    writeln!(
+        out,
+        "/// Creates one `Animation` per wall, set up as the layout file describes, and binds \
+         `$animations`"
+    )?;
+    writeln!(
+        out,
+        "/// to an array of them ready for a `LightingController`. The layout's `bg_rainbow` \
+         settings are"
+    )?;
+    writeln!(out, "/// indices into `$rainbows`.")?;
+    write_macro_start(out, "room_animations")?;
+    for (i, wall) in room.walls.iter().enumerate() {
+        let upper = wall.name.to_uppercase();
+        writeln!(out, "        // {} wall", wall.name)?;
+        write_animation(
+            out,
+            &format!("{}_wall", wall.name),
+            &format!("$crate::room::NUM_LEDS_{upper}_WALL"),
+            &format!("$crate::room::{upper}_TRANSLATION"),
+            room.wall_animation(i),
+        )?;
+    }
+    let borrows: Vec<String> = room
+        .walls
+        .iter()
+        .map(|wall| format!("&mut {}_wall", wall.name))
+        .collect();
+    write_macro_end(out, &borrows.join(", "))?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "/// Creates a single `Animation` running around the whole room through the walls in \
+         `ROOM_LOOP`,"
+    )?;
+    writeln!(
+        out,
+        "/// and binds `$animations` to an array holding it. Takes the same arguments as \
+         `room_animations!`."
+    )?;
+    write_macro_start(out, "room_loop_animation")?;
+    write_animation(
+        out,
+        "room_loop",
+        "$crate::room::NUM_LEDS",
+        "$crate::room::LOOP_TRANSLATION",
+        room.loop_animation(),
+    )?;
+    write_macro_end(out, "&mut room_loop")
+}
+
+fn write_macro_start(out: &mut String, name: &str) -> core::fmt::Result {
+    writeln!(out, "#[macro_export]")?;
+    writeln!(out, "macro_rules! {name} {{")?;
+    writeln!(
+        out,
+        "    ($animations:ident, $frame_rate:expr, $rainbows:expr, $trig_rainbow:expr) => {{"
+    )?;
+    writeln!(out, "        let frame_rate = $frame_rate;")?;
+    writeln!(out, "        let rainbows = $rainbows;")?;
+    writeln!(out, "        let trig_rainbow = $trig_rainbow;")?;
+    writeln!(out)
+}
+
+fn write_macro_end(out: &mut String, borrows: &str) -> core::fmt::Result {
+    writeln!(
+        out,
+        "        let $animations: [&mut dyn {ANIMATIONS}::Animatable; _] = [{borrows}];"
+    )?;
+    writeln!(out, "    }};")?;
+    writeln!(out, "}}")
+}
+
+/// One `let mut {name} = Animation::<{len}>::new(..)...;` statement inside a macro.
+fn write_animation(
+    out: &mut String,
+    name: &str,
+    len: &str,
+    translation: &str,
+    animation: ResolvedAnimation,
+) -> core::fmt::Result {
+    writeln!(
+        out,
+        "        let mut {name} = {ANIMATIONS}::Animation::<{{ {len} }}>::new("
+    )?;
+    writeln!(
+        out,
+        "            ::lighting_controller::default_animations::ANI_DEFAULT,"
+    )?;
+    writeln!(out, "            frame_rate,")?;
+    writeln!(out, "        )")?;
+    writeln!(out, "        .set_translation_array({translation})")?;
+    writeln!(
+        out,
+        "        .set_bg_rainbow(rainbows[{}], {ANIMATIONS}::RainbowDir::Forward)",
+        animation.bg_rainbow
+    )?;
+    writeln!(
+        out,
+        "        .set_bg_duration_ns({}, frame_rate)",
+        grouped(animation.bg_duration_ms * 1_000_000)
+    )?;
+    writeln!(
+        out,
+        "        .set_bg_subdivisions({})",
+        animation.bg_subdivisions
+    )?;
+    writeln!(
+        out,
+        "        .set_trig_duration_ns({}, frame_rate)",
+        grouped(animation.trig_duration_ms * 1_000_000)
+    )?;
+    writeln!(
+        out,
+        "        .set_trig_fade_rainbow(trig_rainbow, {ANIMATIONS}::RainbowDir::Forward)"
+    )?;
+    writeln!(
+        out,
+        "        .set_trig_incremental_rainbow(trig_rainbow, \
+         {ANIMATIONS}::RainbowDir::Forward);"
+    )?;
+    writeln!(out)
+}
+
+fn write_outputs(out: &mut String, room: &Room) -> core::fmt::Result {
+    let indices: Vec<usize> = (0..room.outputs.len()).collect();
+    let names = |prefix: &str| -> String {
+        let names: Vec<String> = indices.iter().map(|i| format!("{prefix}_{i}")).collect();
+        match names.len() {
+            1 => format!("({},)", names[0]),
+            _ => format!("({})", names.join(", ")),
+        }
+    };
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "/// Takes the RMT channel or SPI peripheral and the GPIO pins the layout file gives each \
+         output from"
+    )?;
+    writeln!(
+        out,
+        "/// `$rmt` and `$peripherals`, and binds `$outputs` to a tuple holding them and a buffer \
+         for the"
+    )?;
+    writeln!(
+        out,
+        "/// output's LED adapter, in output order. `$strips` is bound to a tuple of the adapters, \
+         set up"
+    )?;
+    writeln!(out, "/// once on those channels.")?;
+    writeln!(out, "#[macro_export]")?;
+    writeln!(out, "macro_rules! room_outputs {{")?;
+    writeln!(
+        out,
+        "    ($outputs:ident, $strips:ident, $rmt:ident, $peripherals:ident) => {{"
+    )?;
+    writeln!(out, "        let mut $outputs = (")?;
+    for (i, output) in room.outputs.iter().enumerate() {
+        let on_output: Vec<&str> = room
+            .walls
+            .iter()
+            .filter(|wall| wall.output == i)
+            .map(|wall| wall.name.as_str())
+            .collect();
+        writeln!(out, "            // {}", wall_list(&on_output))?;
+        writeln!(out, "            (")?;
+        match (output.rmt_channel, output.clock_pin) {
+            (Some(channel), _) => {
+                writeln!(out, "                $rmt.channel{channel},")?;
+                writeln!(out, "                $peripherals.GPIO{},", output.pin)?;
+                writeln!(
+                    out,
+                    "                ::esp_hal_smartled::smart_led_buffer!("
+                )?;
+                writeln!(
+                    out,
+                    "                    $crate::room::OUTPUTS.outputs[{i}].adapter_len()"
+                )?;
+                writeln!(out, "                ),")?;
+            }
+            (None, Some(clock_pin)) => {
+                writeln!(out, "                $peripherals.SPI2,")?;
+                writeln!(out, "                $peripherals.GPIO{clock_pin},")?;
+                writeln!(out, "                $peripherals.GPIO{},", output.pin)?;
+                writeln!(out, "                [0u8; $crate::apa102::frame_len(")?;
+                writeln!(
+                    out,
+                    "                    $crate::room::OUTPUTS.outputs[{i}].physical_len()"
+                )?;
+                writeln!(out, "                )],")?;
+            }
+            (None, None) => unreachable!("Checked by `Room::validate`."),
+        }
+        writeln!(out, "            ),")?;
+    }
+    writeln!(out, "        );")?;
+    writeln!(out, "        let mut $strips = (")?;
+    for i in &indices {
+        write!(out, "            ")?;
+        write_adapter(out, room, *i, "            ")?;
+        writeln!(out, ",")?;
+    }
+    writeln!(out, "        );")?;
+    writeln!(out, "    }};")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "/// Sends each adapter of `$strips` from `room_outputs!` its colors from `$buffer` if its \
+         entry in"
+    )?;
+    writeln!(
+        out,
+        "/// `$send` is set, with every strip transmitting at once. Evaluates to an array of each \
+         output's"
+    )?;
+    writeln!(out, "/// result.")?;
+    writeln!(out, "#[macro_export]")?;
+    writeln!(out, "macro_rules! room_write_outputs {{")?;
+    writeln!(out, "    ($strips:ident, $buffer:expr, $send:expr) => {{{{")?;
+    writeln!(out, "        let buffer: &[::rgb::RGB8] = $buffer;")?;
+    writeln!(
+        out,
+        "        let send: [bool; $crate::room::NUM_OUTPUTS] = $send;"
+    )?;
+    writeln!(out, "        let {} = &mut $strips;", names("strip"))?;
+    match indices.len() {
+        1 => writeln!(out, "        let result_0 =")?,
+        _ => {
+            writeln!(
+                out,
+                "        let {} = ::embassy_futures::join::join(",
+                names("result")
+            )?;
+        }
+    }
+    for i in &indices {
+        writeln!(out, "            async {{")?;
+        writeln!(out, "                if send[{i}] {{")?;
+        writeln!(
+            out,
+            "                    ::smart_leds::SmartLedsWriteAsync::write("
+        )?;
+        writeln!(out, "                        strip_{i},")?;
+        writeln!(
+            out,
+            "                        $crate::room::OUTPUTS.outputs[{i}].colors(buffer),"
+        )?;
+        writeln!(out, "                    )")?;
+        writeln!(out, "                    .await")?;
+        let kind = match room.outputs[*i].format {
+            Format::Apa102 => "Spi",
+            Format::Rgb | Format::Rgbw => "Rmt",
+        };
+        writeln!(
+            out,
+            "                    .map_err($crate::error::StripError::{kind})"
+        )?;
+        writeln!(out, "                }} else {{")?;
+        writeln!(out, "                    Ok(())")?;
+        writeln!(out, "                }}")?;
+        match indices.len() {
+            1 => writeln!(out, "            }}")?,
+            _ => writeln!(out, "            }},")?,
+        }
+    }
+    match indices.len() {
+        1 => writeln!(out, "            .await;")?,
+        _ => writeln!(out, "        )\n        .await;")?,
+    }
+    let results: Vec<String> = indices.iter().map(|i| format!("result_{i}")).collect();
+    writeln!(
+        out,
+        "        let results: [::core::result::Result<(), $crate::error::StripError<"
+    )?;
+    writeln!(out, "            ::esp_hal_smartled::LedAdapterError,")?;
+    writeln!(
+        out,
+        "            $crate::apa102::Apa102Error<::esp_hal::spi::Error>,"
+    )?;
+    writeln!(
+        out,
+        "        >>; $crate::room::NUM_OUTPUTS] = [{}];",
+        results.join(", ")
+    )?;
+    writeln!(out, "        results")?;
+    writeln!(out, "    }}}};")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "/// Sets the adapter of each output whose entry in `$reinit` is set up on its channel \
+         again,"
+    )?;
+    writeln!(
+        out,
+        "/// leaving the other strips as they are. `$strips` and `$outputs` are the ones from \
+         `room_outputs!`."
+    )?;
+    writeln!(out, "#[macro_export]")?;
+    writeln!(out, "macro_rules! room_reinit_outputs {{")?;
+    writeln!(
+        out,
+        "    ($strips:ident, $outputs:ident, $reinit:expr) => {{{{"
+    )?;
+    writeln!(
+        out,
+        "        let reinit: [bool; $crate::room::NUM_OUTPUTS] = $reinit;"
+    )?;
+    writeln!(
+        out,
+        "        // `$strips` is moved out as a whole, so the old adapters' borrows of their channels \
+         end"
+    )?;
+    writeln!(
+        out,
+        "        // before new ones are taken. Destructuring it in place only moves its fields, \
+         which keeps"
+    )?;
+    writeln!(
+        out,
+        "        // those borrows alive until `$strips` is assigned again."
+    )?;
+    let mut_names: Vec<String> = indices.iter().map(|i| format!("mut strip_{i}")).collect();
+    match mut_names.len() {
+        1 => writeln!(out, "        let ({},) = {{ $strips }};", mut_names[0])?,
+        _ => writeln!(
+            out,
+            "        let ({}) = {{ $strips }};",
+            mut_names.join(", ")
+        )?,
+    }
+    for i in &indices {
+        writeln!(out, "        if reinit[{i}] {{")?;
+        writeln!(out, "            ::core::mem::drop(strip_{i});")?;
+        write!(out, "            strip_{i} = ")?;
+        write_adapter(out, room, *i, "            ")?;
+        writeln!(out, ";")?;
+        writeln!(out, "        }}")?;
+    }
+    writeln!(out, "        $strips = {};", names("strip"))?;
+    writeln!(out, "    }}}};")?;
+    writeln!(out, "}}")
+}
+
+/// The expression setting up output `i`'s LED adapter on its entry in `$outputs`, continuing the
+/// current line and leaving the line open after the closing parenthesis.
+fn write_adapter(out: &mut String, room: &Room, i: usize, indent: &str) -> core::fmt::Result {
+    if room.outputs[i].format != Format::Apa102 {
+        writeln!(out, "::esp_hal_smartled::SmartLedsAdapterAsync::new(")?;
+        writeln!(out, "{indent}    $outputs.{i}.0.reborrow(),")?;
+        writeln!(out, "{indent}    $outputs.{i}.1.reborrow(),")?;
+        writeln!(out, "{indent}    &mut $outputs.{i}.2,")?;
+        return write!(out, "{indent})");
+    }
+    writeln!(out, "$crate::apa102::Apa102Adapter::new(")?;
+    writeln!(out, "{indent}    ::esp_hal::spi::master::Spi::new(")?;
+    writeln!(out, "{indent}        $outputs.{i}.0.reborrow(),")?;
+    writeln!(
+        out,
+        "{indent}        ::esp_hal::spi::master::Config::default().with_frequency("
+    )?;
+    writeln!(
+        out,
+        "{indent}            ::esp_hal::time::Rate::from_khz($crate::apa102::CLOCK_KHZ),"
+    )?;
+    writeln!(out, "{indent}        ),")?;
+    writeln!(out, "{indent}    )")?;
+    writeln!(
+        out,
+        "{indent}    .expect(\"Failed to set up SPI for an APA102 strip\")"
+    )?;
+    writeln!(out, "{indent}    .with_sck($outputs.{i}.1.reborrow())")?;
+    writeln!(out, "{indent}    .with_mosi($outputs.{i}.2.reborrow())")?;
+    writeln!(out, "{indent}    .into_async(),")?;
+    writeln!(out, "{indent}    &mut $outputs.{i}.3,")?;
+    write!(out, "{indent})")
+}
+
+fn write_psu_pin(out: &mut String, room: &Room) -> core::fmt::Result {
+    writeln!(out)?;
+    writeln!(
+        out,
+        "/// Takes the GPIO pin the layout file gives the LED supply switch from `$peripherals`."
+    )?;
+    writeln!(out, "#[macro_export]")?;
+    writeln!(out, "macro_rules! room_psu_pin {{")?;
+    writeln!(out, "    ($peripherals:ident) => {{")?;
+    writeln!(out, "        $peripherals.GPIO{}", room.psu.pin)?;
+    writeln!(out, "    }};")?;
+    writeln!(out, "}}")
+}
+
+fn point(point: Point) -> String {
+    format!("Point::new({}, {}, {})", point.x, point.y, point.z)
+}
+
+/// "closet wall", "closet and window walls", "closet, window and door walls".
+fn wall_list(names: &[&str]) -> String {
+    match names {
+        [name] => format!("{name} wall"),
+        [rest @ .., last] => format!("{} and {last} walls", rest.join(", ")),
+        [] => String::new(),
+    }
+}
+
+/// Formats `n` with underscores between groups of three digits, like the hand-written constants.
+fn grouped(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push('_');
+        }
+        out.push(digit);
+    }
+    out
+}
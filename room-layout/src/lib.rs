@@ -0,0 +1,22 @@
+//! Room layout files for the multi-pin ESP32-C3 example.
+//!
+//! A room is described in a TOML file: its strips, its walls in the order their LEDs appear in the
+//! logical buffer, the supplies feeding the strips and the settings for each wall's animation. The
+//! example's `build.rs` reads the selected file with `parse` and writes the Rust source returned
+//! by `generate` into its `room` module. This crate runs on the host, so the parsing and checking
+//! can be tested without any hardware.
+
+mod config;
+mod error;
+mod generate;
+
+pub use config::*;
+pub use error::Error;
+pub use generate::generate;
+
+/// Parses and checks a layout file.
+pub fn parse(source: &str) -> Result<Room, Error> {
+    let room: Room = toml::from_str(source).map_err(Error::Toml)?;
+    room.validate()?;
+    Ok(room)
+}
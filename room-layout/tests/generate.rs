@@ -0,0 +1,250 @@
+use room_layout::{generate, parse};
+
+const OFFICE: &str = include_str!("../../esp32c3-smart-led-multi-pin/layouts/office_lights.toml");
+
+fn office() -> String {
+    generate(&parse(OFFICE).unwrap(), "layouts/office_lights.toml")
+}
+
+#[test]
+fn names_the_source_file() {
+    assert!(office().starts_with(
+        "// Generated by build.rs from layouts/office_lights.toml, edit that file instead.\n"
+    ));
+}
+
+#[test]
+fn wall_constants() {
+    let generated = office();
+    for line in [
+        "pub const CLOSET_WALL: usize = 0;",
+        "pub const NORTH_WALL: usize = 3;",
+        "pub const NUM_WALLS: usize = 4;",
+        "pub const WALL_NAMES: [&str; NUM_WALLS] = [\"closet\", \"window\", \"door\", \"north\"];",
+        "pub const LAYOUT: Layout<4, 2> = Layout::new([",
+        "    Wall { output: 1, len: 292, reverse: true },",
+        "pub const START_DOOR_INDEX: usize = LAYOUT.start_index(DOOR_WALL);",
+        "pub const NUM_LEDS_WINDOW_WALL: usize = LAYOUT.wall_len(WINDOW_WALL);",
+        "pub const NORTH_TRANSLATION: [usize; NUM_LEDS_NORTH_WALL] =",
+        "    // closet and window walls",
+        "pub const TRANSLATIONS: [&[usize]; NUM_WALLS] = \
+         [&CLOSET_TRANSLATION, &WINDOW_TRANSLATION, &DOOR_TRANSLATION, &NORTH_TRANSLATION];",
+    ] {
+        assert!(generated.lines().any(|l| l == line), "missing `{line}`");
+    }
+}
+
+#[test]
+fn output_settings() {
+    let generated = office();
+    for line in [
+        "pub const OUTPUT_GAMMA: [GammaLut; 2] = \
+         [GammaLut::with_brightness(2.8, 255), GammaLut::with_brightness(2.8, 255)];",
+        "        Supply { outputs: &[1], budget_ma: 8_000 },",
+        "        channel_full_ua: 20_000,",
+        "        idle_ua: 1_000,",
+        "    off_after_black_us: 60_000_000,",
+        "    settle_us: 250_000,",
+        "pub const BG_RAINBOW: usize = 3;",
+        "pub const WALL_BG_DURATIONS_NS: [u64; NUM_WALLS] = \
+         [20_000_000_000, 20_000_000_000, 20_000_000_000, 20_000_000_000];",
+        "pub const LOOP_BG_DURATION_NS: u64 = 40_000_000_000;",
+        "pub const FRAME_RATE_HZ: u32 = 60;",
+    ] {
+        assert!(generated.lines().any(|l| l == line), "missing `{line}`");
+    }
+}
+
+#[test]
+fn brightness_is_baked_into_the_gamma_table() {
+    let source = OFFICE.replacen("pin = 5\n", "pin = 5\nbrightness = 128\n", 1);
+    let generated = generate(&parse(&source).unwrap(), "dim.toml");
+    assert!(
+        generated.contains(
+            "[GammaLut::with_brightness(2.8, 255), GammaLut::with_brightness(2.8, 128)];"
+        )
+    );
+}
+
+#[test]
+fn rgbw_outputs() {
+    // Four bytes per LED take longer to send, so a wall of them needs a lower frame rate.
+    let source = OFFICE
+        .replace("frame_rate_hz = 60", "frame_rate_hz = 40")
+        .replacen(
+            "pin = 6\n",
+            "pin = 6\nformat = \"rgbw\"\nwhite_point = \"warm\"\norder = \"rgb\"\n",
+            1,
+        );
+    let generated = generate(&parse(&source).unwrap(), "rgbw.toml");
+    let rgbw = "        format: PixelFormat::Rgbw(crate::rgbw::RgbwConfig {\n            \
+                strategy: crate::rgbw::WhiteStrategy::Saturation,\n            \
+                white_point: crate::rgbw::WARM_WHITE,\n        }),\n        \
+                order: ColorOrder::Rgb,\n";
+    assert!(generated.contains(rgbw), "{generated}");
+    // The adapter's buffer holds the packed channels rather than one color per LED.
+    assert!(generated.contains("$crate::room::OUTPUTS.outputs[0].adapter_len()"));
+}
+
+#[test]
+fn animation_per_wall() {
+    let generated = office();
+    let (generated, _) = generated
+        .split_once("macro_rules! room_loop_animation")
+        .unwrap();
+    assert!(generated.contains("macro_rules! room_animations {"));
+    assert_eq!(
+        generated.matches(".set_bg_rainbow(rainbows[3], ").count(),
+        4
+    );
+    assert_eq!(
+        generated
+            .matches(".set_bg_duration_ns(20_000_000_000, frame_rate)")
+            .count(),
+        4
+    );
+    assert!(generated.contains(".set_translation_array($crate::room::DOOR_TRANSLATION)"));
+    assert!(
+        generated
+            .contains("[&mut closet_wall, &mut window_wall, &mut door_wall, &mut north_wall];")
+    );
+}
+
+#[test]
+fn positions() {
+    let generated = office();
+    let window = "    // window wall\n    WallGeometry {\n        \
+                  start: Point::new(3367, 17, 0),\n        \
+                  end: Point::new(3367, 4883, 0),\n    },\n";
+    assert!(generated.contains(window), "{generated}");
+    assert!(generated.contains(
+        "pub static SPATIAL_MAP: SpatialMap<NUM_LEDS> = SpatialMap::new(&LAYOUT, WALL_GEOMETRY);"
+    ));
+    assert!(generated.contains("pub const TRIGGER_ORIGIN: Point = Point::new(0, 2442, 0);"));
+}
+
+#[test]
+fn room_loop() {
+    let generated = office();
+    assert!(generated.contains(
+        "pub const ROOM_LOOP: [usize; NUM_WALLS] = \
+         [CLOSET_WALL, WINDOW_WALL, NORTH_WALL, DOOR_WALL];"
+    ));
+    let (_, room_loop) = generated
+        .split_once("macro_rules! room_loop_animation {")
+        .unwrap();
+    assert!(room_loop.contains("Animation::<{ $crate::room::NUM_LEDS }>::new("));
+    assert!(room_loop.contains(".set_translation_array($crate::room::LOOP_TRANSLATION)"));
+    assert!(room_loop.contains(".set_bg_subdivisions(4)"));
+    assert!(room_loop.contains(".set_bg_duration_ns(40_000_000_000, frame_rate)"));
+    assert!(room_loop.contains("[&mut room_loop];"));
+}
+
+#[test]
+fn outputs_use_their_channel_and_pin() {
+    let source = OFFICE
+        .replace("rmt_channel = 0\npin = 6", "rmt_channel = 1\npin = 4")
+        .replace("rmt_channel = 1\npin = 5", "rmt_channel = 0\npin = 3");
+    let generated = generate(&parse(&source).unwrap(), "office.toml");
+    assert!(generated.contains("pub const NUM_OUTPUTS: usize = 2;"));
+    let (_, outputs) = generated.split_once("macro_rules! room_outputs {").unwrap();
+    let first = outputs.find("$rmt.channel1,\n                $peripherals.GPIO4,");
+    let second = outputs.find("$rmt.channel0,\n                $peripherals.GPIO3,");
+    assert!(first.is_some() && first < second, "{outputs}");
+
+    let (outputs, write) = outputs
+        .split_once("macro_rules! room_write_outputs {")
+        .unwrap();
+    // Each adapter is set up once, on its own entry.
+    assert_eq!(
+        outputs
+            .matches("::esp_hal_smartled::SmartLedsAdapterAsync::new(")
+            .count(),
+        2
+    );
+    assert!(outputs.contains("$outputs.1.0.reborrow(),\n                $outputs.1.1.reborrow(),"));
+
+    let (write, reinit) = write
+        .split_once("macro_rules! room_reinit_outputs {")
+        .unwrap();
+    assert!(write.contains("let (strip_0, strip_1) = &mut $strips;"));
+    assert!(write.contains("::embassy_futures::join::join("));
+    assert!(write.contains("$crate::room::OUTPUTS.outputs[1].colors(buffer),"));
+    assert!(write.contains("[result_0, result_1]"));
+    assert!(!write.contains("SmartLedsAdapterAsync::new("));
+
+    // Only the strips asked for are set up again.
+    assert!(reinit.contains("let (mut strip_0, mut strip_1) = { $strips };"));
+    assert!(reinit.contains("if reinit[1] {\n            ::core::mem::drop(strip_1);"));
+    assert!(reinit.contains("$strips = (strip_0, strip_1);"));
+}
+
+#[test]
+fn apa102_outputs_are_driven_from_spi() {
+    let source = OFFICE.replace(
+        "rmt_channel = 1\npin = 5",
+        "format = \"apa102\"\npin = 5\nclock_pin = 4",
+    );
+    let generated = generate(&parse(&source).unwrap(), "office.toml");
+    assert!(
+        generated
+            .lines()
+            .any(|l| l == "        driver: Driver::Spi { data_pin: 5, clock_pin: 4 },")
+    );
+    // Their adapter takes colors as they are.
+    assert_eq!(
+        generated
+            .matches("        order: ColorOrder::Grb,\n")
+            .count(),
+        2
+    );
+    let (_, outputs) = generated.split_once("macro_rules! room_outputs {").unwrap();
+    let (outputs, write) = outputs
+        .split_once("macro_rules! room_write_outputs {")
+        .unwrap();
+    assert!(outputs.contains("$peripherals.SPI2,\n                $peripherals.GPIO4,"));
+    assert!(outputs.contains("$crate::apa102::Apa102Adapter::new("));
+    assert!(outputs.contains(".with_sck($outputs.1.1.reborrow())"));
+    assert!(outputs.contains(".with_mosi($outputs.1.2.reborrow())"));
+    assert_eq!(
+        outputs
+            .matches("::esp_hal_smartled::SmartLedsAdapterAsync::new(")
+            .count(),
+        1
+    );
+    assert!(write.contains(".map_err($crate::error::StripError::Rmt)"));
+    assert!(write.contains(".map_err($crate::error::StripError::Spi)"));
+}
+
+#[test]
+fn psu_pin() {
+    let generated = office();
+    let (_, psu) = generated.split_once("macro_rules! room_psu_pin {").unwrap();
+    assert!(psu.contains("        $peripherals.GPIO7\n"));
+
+    let source = OFFICE.replace("[psu]\npin = 7", "[psu]\npin = 3");
+    let generated = generate(&parse(&source).unwrap(), "office.toml");
+    let (_, psu) = generated.split_once("macro_rules! room_psu_pin {").unwrap();
+    assert!(psu.contains("        $peripherals.GPIO3\n"));
+}
+
+#[test]
+fn missing_leds() {
+    let generated = office();
+    assert_eq!(generated.matches("        leds: None,\n").count(), 2);
+    assert!(!generated.contains("OUTPUT_0_LEDS"));
+
+    let source = OFFICE.replace(
+        "missing = []\nforce_black = []",
+        "missing = [1]\nforce_black = [3]",
+    );
+    let generated = generate(&parse(&source).unwrap(), "office.toml");
+    for line in [
+        "const OUTPUT_0_LEDS: [PhysicalLed; 494] = [",
+        "    PhysicalLed::Logical(0), PhysicalLed::Logical(2), PhysicalLed::Black,",
+        "        leds: Some(&OUTPUT_0_LEDS),",
+        "        leds: None,",
+    ] {
+        assert!(generated.lines().any(|l| l == line), "missing `{line}`");
+    }
+}
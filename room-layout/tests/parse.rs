@@ -0,0 +1,475 @@
+use room_layout::{Error, Format, PhysicalLed, Point, ResolvedAnimation, WhitePoint, parse};
+
+const OFFICE: &str = include_str!("../../esp32c3-smart-led-multi-pin/layouts/office_lights.toml");
+const TEST_STRIP: &str = include_str!("../../esp32c3-smart-led-multi-pin/layouts/test_strip.toml");
+
+// Two strips with one wall each, everything else left at the defaults.
+const MINIMAL: &str = r#"
+[[output]]
+rmt_channel = 0
+pin = 6
+
+[[output]]
+rmt_channel = 1
+pin = 5
+
+[[wall]]
+name = "left"
+output = 0
+len = 10
+start = [0, 0]
+end = [900, 0]
+
+[[wall]]
+name = "right"
+output = 1
+len = 20
+start = [1000, 0, 2000]
+end = [1000, 1900, 2000]
+
+[[supply]]
+outputs = [0, 1]
+budget_ma = 1_000
+"#;
+
+fn layout_error(source: &str) -> String {
+    match parse(source) {
+        Err(Error::Layout(message)) => message,
+        Err(error) => panic!("expected a layout error, got {error}"),
+        Ok(_) => panic!("expected a layout error, the file parsed"),
+    }
+}
+
+#[test]
+fn office_layout_matches_the_room() {
+    let room = parse(OFFICE).unwrap();
+    assert_eq!(room.walls.len(), 4);
+    assert_eq!(room.num_leds(), 989);
+    assert_eq!(room.start_index(2), 495);
+    assert_eq!(room.start_index(3), 787);
+    assert!(room.walls[2].reverse && room.walls[3].reverse);
+    assert_eq!(room.outputs[0].gamma, 2.8);
+    assert_eq!(room.psu.off_after_black_ms, 60_000);
+    let animation = ResolvedAnimation {
+        bg_rainbow: 3,
+        bg_duration_ms: 20_000,
+        bg_subdivisions: 2,
+        trig_duration_ms: 5_000,
+    };
+    for wall in 0..4 {
+        assert_eq!(room.wall_animation(wall), animation);
+    }
+}
+
+#[test]
+fn test_strip_walls_override_the_room_animation() {
+    let room = parse(TEST_STRIP).unwrap();
+    assert_eq!(room.num_leds(), 212);
+    let rainbows: Vec<usize> = (0..4)
+        .map(|wall| room.wall_animation(wall).bg_rainbow)
+        .collect();
+    assert_eq!(rainbows, [3, 2, 1, 0]);
+    assert_eq!(room.wall_animation(0).bg_duration_ms, 3_000);
+}
+
+#[test]
+fn defaults() {
+    let room = parse(MINIMAL).unwrap();
+    let output = &room.outputs[0];
+    assert!(!output.dither && !output.reverse);
+    assert_eq!(output.format, Format::Rgb);
+    assert_eq!(output.order, None);
+    assert_eq!(output.gamma, 2.8);
+    assert_eq!(output.brightness, 255);
+    assert_eq!(room.walls[0].correction.brightness, 255);
+    assert!(room.psu.active_high);
+    assert_eq!(room.psu.pin, 7);
+    assert_eq!(room.wall_animation(1).bg_duration_ms, 20_000);
+    assert_eq!(room.frame_rate_hz, 60);
+    assert_eq!(room.power.channel_full_ua, 20_000);
+    assert_eq!(room.power.idle_ua, 1_000);
+}
+
+#[test]
+fn power_model() {
+    let source = format!("{MINIMAL}\n[power]\nchannel_full_ua = 12_000\nidle_ua = 600\n");
+    let room = parse(&source).unwrap();
+    assert_eq!(room.power.channel_full_ua, 12_000);
+    assert_eq!(room.power.idle_ua, 600);
+
+    let source = format!("{MINIMAL}\n[power]\nidle_ua = 600\n");
+    assert_eq!(parse(&source).unwrap().power.channel_full_ua, 20_000);
+
+    let source = format!("{MINIMAL}\n[power]\nchannel_full_ua = 0\n");
+    assert_eq!(
+        layout_error(&source),
+        "power.channel_full_ua has to be more than 0, or no frame is ever over budget"
+    );
+}
+
+#[test]
+fn frame_rate_is_checked() {
+    let source = format!("frame_rate_hz = 0\n{MINIMAL}");
+    assert_eq!(layout_error(&source), "frame_rate_hz has to be at least 1");
+
+    // 20 LEDs at 30us each only just fit in a frame at 1666 Hz.
+    let source = format!("frame_rate_hz = 1666\n{MINIMAL}");
+    assert!(parse(&source).is_ok());
+    let source = format!("frame_rate_hz = 1700\n{MINIMAL}");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 takes 600us to send, longer than a frame at 1700 Hz"
+    );
+}
+
+#[test]
+fn toml_errors_point_at_the_line() {
+    let source = MINIMAL.replace("len = 20", "len = twenty");
+    let error = parse(&source).unwrap_err();
+    assert!(matches!(error, Error::Toml(_)));
+    assert!(error.to_string().contains("line 20, column 7"), "{error}");
+
+    let source = MINIMAL.replace("pin = 5", "pin = 5\ncolour = \"red\"");
+    let error = parse(&source).unwrap_err().to_string();
+    assert!(error.contains("unknown field `colour`"), "{error}");
+}
+
+#[test]
+fn outputs_need_their_own_rmt_channel_and_pin() {
+    let source = format!("{MINIMAL}\n[[output]]\nrmt_channel = 2\npin = 4\n");
+    assert_eq!(
+        layout_error(&source),
+        "output 2 uses RMT channel 2, but the ESP32-C3 can only transmit on channels 0 and 1"
+    );
+
+    let source = MINIMAL.replace("pin = 5", "pin = 22");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 uses GPIO 22, but the ESP32-C3 has no such pin"
+    );
+    let source = MINIMAL.replace("pin = 5", "pin = 7");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 uses GPIO 7, which is used by the LED supply switch"
+    );
+    let source = MINIMAL.replace("pin = 6", "pin = 2");
+    assert_eq!(
+        layout_error(&source),
+        "output 0 uses GPIO 2, which is used by button 2"
+    );
+}
+
+#[test]
+fn single_output_room() {
+    let source = MINIMAL
+        .replace("[[output]]\nrmt_channel = 1\npin = 5\n", "")
+        .replace("output = 1\n", "output = 0\n")
+        .replace("outputs = [0, 1]", "outputs = [0]");
+    let room = parse(&source).unwrap();
+    assert_eq!(room.outputs.len(), 1);
+    assert_eq!(room.output_len(0), 30);
+
+    let source = source.replace("[[output]]\nrmt_channel = 0\npin = 6\n", "");
+    let source = format!("output = []\n{source}");
+    assert_eq!(layout_error(&source), "the room has no outputs");
+}
+
+#[test]
+fn outputs_are_checked() {
+    let source = MINIMAL.replace("rmt_channel = 1", "rmt_channel = 0");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 uses the same RMT channel as an earlier output"
+    );
+
+    let source = MINIMAL.replace("pin = 5", "pin = 6");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 uses the same pin as an earlier output"
+    );
+
+    let source = MINIMAL.replace("pin = 5", "pin = 5\ngamma = 0.0");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 gamma has to be a positive number"
+    );
+
+    let source = MINIMAL.replace("pin = 5", "pin = 5\nwhite_point = \"warm\"");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 white_strategy and white_point only apply to rgbw outputs"
+    );
+}
+
+#[test]
+fn rgbw_outputs() {
+    let source = MINIMAL.replace(
+        "pin = 5",
+        "pin = 5\nformat = \"rgbw\"\nwhite_point = \"warm\"",
+    );
+    let room = parse(&source).unwrap();
+    assert_eq!(room.outputs[1].format, Format::Rgbw);
+    assert_eq!(room.outputs[1].white_point, Some(WhitePoint::Warm));
+    assert_eq!(room.outputs[1].white_strategy, None);
+}
+
+#[test]
+fn apa102_outputs() {
+    let apa102 = "[[output]]\nformat = \"apa102\"\npin = 5\nclock_pin = 4";
+    let source = MINIMAL.replace("[[output]]\nrmt_channel = 1\npin = 5", apa102);
+    let room = parse(&source).unwrap();
+    assert_eq!(room.outputs[1].format, Format::Apa102);
+    assert_eq!(room.outputs[1].rmt_channel, None);
+    assert_eq!(room.outputs[1].pins().collect::<Vec<_>>(), [5, 4]);
+
+    let missing_channel = MINIMAL.replace("rmt_channel = 1\n", "");
+    assert_eq!(
+        layout_error(&missing_channel),
+        "output 1 needs an rmt_channel"
+    );
+    let with_channel = source.replace("clock_pin = 4", "clock_pin = 4\nrmt_channel = 1");
+    assert_eq!(
+        layout_error(&with_channel),
+        "output 1 is an apa102 strip, which is driven from SPI rather than an RMT channel"
+    );
+    let no_clock = source.replace("\nclock_pin = 4", "");
+    assert_eq!(
+        layout_error(&no_clock),
+        "output 1 is an apa102 strip, which needs a clock_pin"
+    );
+    let rmt_clock = MINIMAL.replace("pin = 5", "pin = 5\nclock_pin = 4");
+    assert_eq!(
+        layout_error(&rmt_clock),
+        "output 1 clock_pin only applies to apa102 outputs"
+    );
+    let ordered = source.replace("clock_pin = 4", "clock_pin = 4\norder = \"rgb\"");
+    assert_eq!(
+        layout_error(&ordered),
+        "output 1 order only applies to one-wire strips, apa102 strips always take blue, green, \
+         red"
+    );
+    let two = MINIMAL
+        .replace(
+            "rmt_channel = 0\npin = 6",
+            "format = \"apa102\"\npin = 6\nclock_pin = 3",
+        )
+        .replace("[[output]]\nrmt_channel = 1\npin = 5", apa102);
+    assert_eq!(
+        layout_error(&two),
+        "output 1 is a second apa102 strip, but the ESP32-C3 only has one SPI peripheral to \
+         drive them from"
+    );
+
+    // The clock pin is checked like any other.
+    let same_pin = source.replace("clock_pin = 4", "clock_pin = 5");
+    assert_eq!(
+        layout_error(&same_pin),
+        "output 1 uses the same pin for data and clock"
+    );
+    let shared = source.replace("clock_pin = 4", "clock_pin = 6");
+    assert_eq!(
+        layout_error(&shared),
+        "output 1 uses the same pin as an earlier output"
+    );
+    let reserved = source.replace("clock_pin = 4", "clock_pin = 18");
+    assert_eq!(
+        layout_error(&reserved),
+        "output 1 uses GPIO 18, which is used by USB"
+    );
+}
+
+#[test]
+fn walls_are_checked() {
+    let source = MINIMAL.replace("\"right\"", "\"left\"");
+    assert_eq!(
+        layout_error(&source),
+        "wall 1 (`left`) name is already used by an earlier wall"
+    );
+
+    let source = MINIMAL.replace("\"right\"", "\"Right\"");
+    assert!(layout_error(&source).starts_with("wall 1 (`Right`) name has to start"));
+
+    let source = MINIMAL.replace("len = 20", "len = 0");
+    assert_eq!(layout_error(&source), "wall 1 (`right`) has no LEDs");
+
+    let source = MINIMAL.replace("output = 1\nlen", "output = 2\nlen");
+    assert_eq!(
+        layout_error(&source),
+        "wall 1 (`right`) is on output 2, but the room only has 2 outputs"
+    );
+}
+
+#[test]
+fn walls_cover_every_output_in_order() {
+    let source = MINIMAL.replace("output = 0\nlen", "output = 1\nlen");
+    assert_eq!(layout_error(&source), "output 0 has no walls");
+
+    let source = MINIMAL.replace("output = 1\nlen", "output = 0\nlen");
+    assert_eq!(layout_error(&source), "output 1 has no walls");
+
+    let source = format!(
+        "{MINIMAL}\n[[wall]]\nname = \"extra\"\noutput = 0\nlen = 5\nstart = [0, 0]\nend = [0, 0]\n"
+    );
+    assert_eq!(
+        layout_error(&source),
+        "wall 2 (`extra`) is on output 0 after a wall on output 1, walls have to be listed in \
+         output order"
+    );
+}
+
+#[test]
+fn supplies_are_checked() {
+    let source = MINIMAL.replace("outputs = [0, 1]", "outputs = [0]");
+    assert_eq!(layout_error(&source), "output 1 isn't fed by any supply");
+
+    let source = MINIMAL.replace("outputs = [0, 1]", "outputs = [0, 1, 1]");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 is listed on more than one supply"
+    );
+
+    let source = MINIMAL.replace("outputs = [0, 1]", "outputs = [0, 2]");
+    assert_eq!(
+        layout_error(&source),
+        "supply 0 feeds output 2, but the room only has 2 outputs"
+    );
+
+    let source = format!("{MINIMAL}\n[[supply]]\noutputs = []\nbudget_ma = 1\n");
+    assert_eq!(layout_error(&source), "supply 1 feeds no outputs");
+}
+
+#[test]
+fn psu_pin_is_checked() {
+    let source = format!("{MINIMAL}\n[psu]\npin = 3\n");
+    assert_eq!(parse(&source).unwrap().psu.pin, 3);
+    // Moving the supply switch frees up its default pin for a strip.
+    let source = format!(
+        "{}\n[psu]\npin = 3\n",
+        MINIMAL.replace("pin = 5", "pin = 7")
+    );
+    assert!(parse(&source).is_ok());
+
+    let source = format!("{MINIMAL}\n[psu]\npin = 5\n");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 uses GPIO 5, which is used by the LED supply switch"
+    );
+    let source = format!("{MINIMAL}\n[psu]\npin = 22\n");
+    assert_eq!(
+        layout_error(&source),
+        "the psu uses GPIO 22, but the ESP32-C3 has no such pin"
+    );
+    let source = format!("{MINIMAL}\n[psu]\npin = 1\n");
+    assert_eq!(
+        layout_error(&source),
+        "the psu uses GPIO 1, which is used by button 1"
+    );
+}
+
+#[test]
+fn positions() {
+    let room = parse(OFFICE).unwrap();
+    assert_eq!(room.trigger_origin, Point { x: 0, y: 2442, z: 0 });
+    assert_eq!(room.walls[1].end, Point { x: 3367, y: 4883, z: 0 });
+
+    let room = parse(MINIMAL).unwrap();
+    assert_eq!(room.trigger_origin, Point::default());
+    assert_eq!(room.walls[1].start, Point { x: 1000, y: 0, z: 2000 });
+
+    let source = MINIMAL.replace("end = [900, 0]", "end = [900]");
+    let error = parse(&source).unwrap_err().to_string();
+    assert!(error.contains("line 15"), "{error}");
+    assert!(
+        error.contains("positions are written [x, y] or [x, y, z]"),
+        "{error}"
+    );
+
+    let source = MINIMAL.replace("end = [900, 0]\n", "");
+    let error = parse(&source).unwrap_err().to_string();
+    assert!(error.contains("missing field `end`"), "{error}");
+}
+
+#[test]
+fn room_loop() {
+    let room = parse(OFFICE).unwrap();
+    assert_eq!(room.loop_walls(), [0, 1, 3, 2]);
+    let animation = room.loop_animation();
+    assert_eq!(animation.bg_subdivisions, 4);
+    assert_eq!(animation.bg_duration_ms, 40_000);
+    assert_eq!(animation.bg_rainbow, 3);
+
+    let room = parse(MINIMAL).unwrap();
+    assert_eq!(room.loop_walls(), [0, 1]);
+    assert_eq!(room.loop_animation(), room.wall_animation(0));
+
+    let source = format!("{MINIMAL}\n[loop]\nwalls = [\"right\", \"left\"]\n");
+    assert_eq!(parse(&source).unwrap().loop_walls(), [1, 0]);
+
+    let source = format!("{MINIMAL}\n[loop]\nwalls = [\"right\", \"middle\"]\n");
+    assert_eq!(layout_error(&source), "the loop has no wall named `middle`");
+
+    let source = format!("{MINIMAL}\n[loop]\nwalls = [\"right\", \"left\", \"right\"]\n");
+    assert_eq!(
+        layout_error(&source),
+        "the loop goes through wall `right` more than once"
+    );
+
+    let source = format!("{MINIMAL}\n[loop]\nwalls = [\"right\"]\n");
+    assert_eq!(
+        layout_error(&source),
+        "the loop doesn't go through wall `left`"
+    );
+}
+
+#[test]
+fn missing_and_forced_black_leds() {
+    let room = parse(MINIMAL).unwrap();
+    assert!(!room.has_masked_leds(0));
+    let logical: Vec<PhysicalLed> = (10..30).map(PhysicalLed::Logical).collect();
+    assert_eq!(room.physical_leds(1), logical);
+
+    // The LED at position 3 was bypassed, so the strip's fourth LED shows logical LED 4.
+    let source = MINIMAL.replace("pin = 6", "pin = 6\nmissing = [3, 7]\nforce_black = [0]");
+    let room = parse(&source).unwrap();
+    assert!(room.has_masked_leds(0));
+    let leds = room.physical_leds(0);
+    assert_eq!(leds.len(), 8);
+    assert_eq!(leds[0], PhysicalLed::Black);
+    assert_eq!(&leds[1..4], [1, 2, 4].map(PhysicalLed::Logical));
+    assert_eq!(&leds[6..], [8, 9].map(PhysicalLed::Logical));
+
+    // Positions count from the strip's first LED, whichever way round it is.
+    let source = MINIMAL.replace("pin = 5", "pin = 5\nreverse = true\nmissing = [0]");
+    let leds = parse(&source).unwrap().physical_leds(1);
+    assert_eq!(leds.len(), 19);
+    assert_eq!(leds[0], PhysicalLed::Logical(28));
+    assert_eq!(leds[18], PhysicalLed::Logical(10));
+}
+
+#[test]
+fn missing_and_forced_black_leds_are_checked() {
+    let source = MINIMAL.replace("pin = 6", "pin = 6\nmissing = [10]");
+    assert_eq!(
+        layout_error(&source),
+        "output 0 missing position 10 is past the end of its 10 LEDs"
+    );
+
+    let source = MINIMAL.replace("pin = 5", "pin = 5\nforce_black = [4, 4]");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 force_black lists position 4 more than once"
+    );
+
+    let source = MINIMAL.replace("pin = 5", "pin = 5\nmissing = [4]\nforce_black = [4]");
+    assert_eq!(
+        layout_error(&source),
+        "output 1 position 4 is both missing and forced black"
+    );
+
+    let missing: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    let source = MINIMAL.replace(
+        "pin = 6",
+        &format!("pin = 6\nmissing = [{}]", missing.join(", ")),
+    );
+    assert_eq!(layout_error(&source), "output 0 has every LED missing");
+}